@@ -0,0 +1,56 @@
+use crate::calculation::{EnumRejection, Position};
+use crate::engine::{Engine, EnumOrder};
+use crate::parser::CSVParsed;
+use futures_util::stream::{Stream, StreamExt};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[cfg(test)]
+#[path = "test/async_engine_test.rs"]
+mod async_engine_test;
+
+// Shares one engine between the tasks of a service, every transaction is applied exactly as
+// `Engine::process` does, in the order the tasks get the lock
+#[derive(Clone, Default)]
+pub struct AsyncEngine {
+    engine: Arc<Mutex<Engine>>,
+}
+
+impl AsyncEngine {
+    pub fn new(engine: Engine) -> AsyncEngine {
+        AsyncEngine {
+            engine: Arc::new(Mutex::new(engine)),
+        }
+    }
+
+    pub async fn process(&self, transaction: &CSVParsed) -> Result<(), EnumRejection> {
+        self.engine.lock().await.process(transaction)
+    }
+
+    // Applies the transactions of the stream in order, the outcome of each comes out of the returned stream
+    pub fn process_stream<'a, S>(
+        &'a self,
+        transactions: S,
+    ) -> impl Stream<Item = (CSVParsed, Result<(), EnumRejection>)> + 'a
+    where
+        S: Stream<Item = CSVParsed> + 'a,
+    {
+        transactions.then(move |transaction| async move {
+            let result = self.process(&transaction).await;
+            (transaction, result)
+        })
+    }
+
+    pub async fn position(&self, client: u16) -> Option<Position> {
+        self.engine.lock().await.position(client)
+    }
+
+    pub async fn positions(&self, order: EnumOrder) -> Vec<Position> {
+        // Only the positions are copied under the lock, the sort happens after it is released
+        let mut positions = self.engine.lock().await.positions();
+        if order == EnumOrder::Client {
+            positions.sort_unstable_by_key(|position| position.client);
+        }
+        positions
+    }
+}
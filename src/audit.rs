@@ -0,0 +1,152 @@
+use crate::calculation::{EnumRejection, EnumStatus, Position};
+use crate::parser::CSVOutput;
+use crate::{CSVParsed, EnumError, EnumType};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::io::Write;
+
+#[cfg(test)]
+#[path = "test/audit_test.rs"]
+mod audit_test;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnumAuditFormat {
+    Csv,
+    Jsonl,
+}
+
+impl TryFrom<String> for EnumAuditFormat {
+    type Error = EnumError;
+
+    fn try_from(format: String) -> Result<Self, Self::Error> {
+        match format.as_str() {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(EnumError::InvalidArgument(format)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum EnumOutcome {
+    Applied,
+    Rejected,
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    r#type: &'a EnumType,
+    client: u16,
+    tx: u32,
+    amount: Option<Decimal>,
+    destination: Option<u16>,
+    outcome: EnumOutcome,
+    reason: Option<&'a EnumRejection>,
+}
+
+impl<'a> AuditRecord<'a> {
+    fn new(transaction: &'a CSVParsed, result: &'a Result<(), EnumRejection>) -> Self {
+        Self {
+            r#type: &transaction.r#type,
+            client: transaction.client,
+            tx: transaction.transaction_id,
+            amount: transaction.amount,
+            destination: transaction.destination,
+            outcome: match result {
+                Ok(()) => EnumOutcome::Applied,
+                Err(_) => EnumOutcome::Rejected,
+            },
+            reason: result.as_ref().err(),
+        }
+    }
+}
+
+// Flattened by hand, the csv writer does not support serde(flatten)
+#[derive(Serialize)]
+struct ReplayRecord<'a> {
+    r#type: &'a EnumType,
+    client: u16,
+    tx: u32,
+    amount: Option<Decimal>,
+    destination: Option<u16>,
+    outcome: EnumOutcome,
+    reason: Option<&'a EnumRejection>,
+    available: String,
+    held: String,
+    total: String,
+    locked: bool,
+    status: Option<EnumStatus>,
+}
+
+enum AuditSink<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Jsonl(W),
+}
+
+pub struct AuditLog<W: Write> {
+    sink: AuditSink<W>,
+}
+
+impl<W: Write> AuditLog<W> {
+    pub fn new(writer: W, format: EnumAuditFormat) -> AuditLog<W> {
+        Self {
+            sink: match format {
+                EnumAuditFormat::Csv => AuditSink::Csv(Box::new(csv::Writer::from_writer(writer))),
+                EnumAuditFormat::Jsonl => AuditSink::Jsonl(writer),
+            },
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        transaction: &CSVParsed,
+        result: &Result<(), EnumRejection>,
+    ) -> Result<(), EnumError> {
+        self.write(&AuditRecord::new(transaction, result))
+    }
+
+    pub fn replay(
+        &mut self,
+        transaction: &CSVParsed,
+        result: &Result<(), EnumRejection>,
+        position: Position,
+    ) -> Result<(), EnumError> {
+        let record = AuditRecord::new(transaction, result);
+        let position = CSVOutput::from(position);
+
+        self.write(&ReplayRecord {
+            r#type: record.r#type,
+            client: record.client,
+            tx: record.tx,
+            amount: record.amount,
+            destination: record.destination,
+            outcome: record.outcome,
+            reason: record.reason,
+            available: position.available,
+            held: position.held,
+            total: position.total,
+            locked: position.locked,
+            status: position.status,
+        })
+    }
+
+    fn write<T: Serialize>(&mut self, record: &T) -> Result<(), EnumError> {
+        match &mut self.sink {
+            AuditSink::Csv(writer) => writer
+                .serialize(record)
+                .map_err(|_| EnumError::CannotWriteLine),
+            AuditSink::Jsonl(writer) => serde_json::to_writer(&mut *writer, record)
+                .map_err(|_| EnumError::CannotWriteLine)
+                .and_then(|_| writeln!(writer).map_err(|_| EnumError::CannotWriteLine)),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), EnumError> {
+        match &mut self.sink {
+            AuditSink::Csv(writer) => writer.flush(),
+            AuditSink::Jsonl(writer) => writer.flush(),
+        }
+        .map_err(|_| EnumError::CannotWriteLine)
+    }
+}
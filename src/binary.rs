@@ -0,0 +1,135 @@
+// Compact binary transaction dump.
+//
+// The input is a sequence of records, each one a little-endian `u16` length followed by a payload
+// of that many bytes:
+//
+//   offset  size  field
+//   0       1     type: 0 deposit, 1 withdrawal, 2 dispute, 3 resolve, 4 chargeback,
+//                 5 unlock, 6 freeze, 7 close, 8 reopen, 9 transfer
+//   1       2     client, little-endian u16
+//   3       4     tx, little-endian u32
+//   7       8     amount mantissa, little-endian i64  (only in 16 and 18 bytes payloads)
+//   15      1     amount scale, 0 to 28               (only in 16 and 18 bytes payloads)
+//   16      2     destination, little-endian u16      (only in 18 bytes payloads)
+//
+// A 7 bytes payload is a row without amount, the amount is `mantissa / 10^scale`.
+// Records are numbered from 1 and the number is reported as the line of an error.
+use crate::parser::{validate, EnumPrecision};
+use crate::{CSVParsed, EnumError, EnumType};
+use rust_decimal::Decimal;
+use std::io::{BufReader, ErrorKind, Read};
+
+#[cfg(test)]
+#[path = "test/binary_test.rs"]
+mod binary_test;
+
+const WITHOUT_AMOUNT: usize = 7;
+const WITH_AMOUNT: usize = 16;
+const WITH_DESTINATION: usize = 18;
+const MAX_SCALE: u8 = 28;
+
+pub fn read_transactions<R: Read>(
+    reader: R,
+    precision: EnumPrecision,
+) -> impl Iterator<Item = Result<CSVParsed, EnumError>> {
+    let mut reader = BufReader::new(reader);
+    let mut record = 0;
+    let mut failed = false;
+
+    std::iter::from_fn(move || {
+        // A broken length prefix leaves the stream out of sync, so nothing after it is read
+        if failed {
+            return None;
+        }
+        record += 1;
+
+        let result = read_record(&mut reader, record)
+            .transpose()?
+            .and_then(|transaction| validate(transaction, record, precision));
+        failed = matches!(
+            result,
+            Err(EnumError::InvalidBinary { .. } | EnumError::CannotReadInput(_))
+        );
+
+        Some(result)
+    })
+}
+
+fn read_record<R: Read>(reader: &mut R, record: u64) -> Result<Option<CSVParsed>, EnumError> {
+    let invalid = |message: String| EnumError::InvalidBinary { record, message };
+
+    let mut length = [0; 2];
+    match reader.read_exact(&mut length[..1]) {
+        Ok(()) => (),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(EnumError::CannotReadInput(err.to_string())),
+    }
+    read_bytes(reader, &mut length[1..], record)?;
+
+    let length = u16::from_le_bytes(length) as usize;
+    if ![WITHOUT_AMOUNT, WITH_AMOUNT, WITH_DESTINATION].contains(&length) {
+        return Err(invalid(format!(
+            "payload of {} bytes, expected {}, {} or {}",
+            length, WITHOUT_AMOUNT, WITH_AMOUNT, WITH_DESTINATION
+        )));
+    }
+
+    let mut payload = [0; WITH_DESTINATION];
+    read_bytes(reader, &mut payload[..length], record)?;
+
+    let r#type = match payload[0] {
+        0 => EnumType::Deposit,
+        1 => EnumType::Withdrawal,
+        2 => EnumType::Dispute,
+        3 => EnumType::Resolve,
+        4 => EnumType::Chargeback,
+        5 => EnumType::Unlock,
+        6 => EnumType::Freeze,
+        7 => EnumType::Close,
+        8 => EnumType::Reopen,
+        9 => EnumType::Transfer,
+        value => {
+            return Err(EnumError::InvalidType {
+                line: record,
+                value: value.to_string(),
+            })
+        }
+    };
+
+    let amount = if length >= WITH_AMOUNT {
+        let mantissa = i64::from_le_bytes(payload[7..15].try_into().unwrap_or_default());
+        let scale = payload[15];
+        if scale > MAX_SCALE {
+            return Err(EnumError::InvalidAmount {
+                line: record,
+                value: format!("{}e-{}", mantissa, scale),
+                reason: format!("scale above {}", MAX_SCALE),
+            });
+        }
+        Some(Decimal::new(mantissa, scale as u32))
+    } else {
+        None
+    };
+
+    Ok(Some(CSVParsed {
+        r#type,
+        client: u16::from_le_bytes([payload[1], payload[2]]),
+        transaction_id: u32::from_le_bytes([payload[3], payload[4], payload[5], payload[6]]),
+        amount,
+        destination: if length == WITH_DESTINATION {
+            Some(u16::from_le_bytes([payload[16], payload[17]]))
+        } else {
+            None
+        },
+    }))
+}
+
+fn read_bytes<R: Read>(reader: &mut R, buffer: &mut [u8], record: u64) -> Result<(), EnumError> {
+    reader.read_exact(buffer).map_err(|err| match err.kind() {
+        ErrorKind::UnexpectedEof => EnumError::InvalidBinary {
+            record,
+            message: "truncated record".to_string(),
+        },
+        _ => EnumError::CannotReadInput(err.to_string()),
+    })
+}
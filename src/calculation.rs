@@ -1,19 +1,141 @@
-use crate::{CSVParsed, EnumType};
+use crate::store::{EnumState, TransactionStore};
+use crate::{CSVParsed, EnumError, EnumType};
 use rust_decimal::Decimal;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::ops::{Add, Sub};
 
 #[cfg(test)]
 #[path = "test/calculation_test.rs"]
 mod calculation_test;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub client: u16,
     pub available: Decimal,
     pub held: Decimal,
     pub total: Decimal,
-    pub locked: bool,
+    pub status: EnumStatus,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnumStatus {
+    #[default]
+    Active,
+    // After a chargeback, nothing is applied until an operator unlocks the account
+    Locked,
+    // Set by an operator, only withdrawals are refused
+    Frozen,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EnumWithdrawalDispute {
+    #[default]
+    Reject,
+    // The withdrawn amount is held while disputed and given back to the client on a chargeback
+    Reverse,
+}
+
+impl TryFrom<String> for EnumWithdrawalDispute {
+    type Error = EnumError;
+
+    fn try_from(policy: String) -> Result<Self, Self::Error> {
+        match policy.as_str() {
+            "reject" => Ok(Self::Reject),
+            "reverse" => Ok(Self::Reverse),
+            _ => Err(EnumError::InvalidArgument(policy)),
+        }
+    }
+}
+
+// What a dispute of more than the available funds does
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EnumNegativeBalance {
+    // The available funds go negative and the client shows up in the exposure report
+    #[default]
+    Allow,
+    Reject,
+    // Only the available funds are held, the rest is a shortfall in the exposure report
+    HoldAvailable,
+}
+
+impl TryFrom<String> for EnumNegativeBalance {
+    type Error = EnumError;
+
+    fn try_from(policy: String) -> Result<Self, Self::Error> {
+        match policy.as_str() {
+            "allow" => Ok(Self::Allow),
+            "reject" => Ok(Self::Reject),
+            "hold-available" => Ok(Self::HoldAvailable),
+            _ => Err(EnumError::InvalidArgument(policy)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub allow_redispute: bool,
+    pub withdrawal_dispute: EnumWithdrawalDispute,
+    pub negative_balance: EnumNegativeBalance,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnumRejection {
+    DuplicateTransaction,
+    MissingAmount,
+    InsufficientFunds,
+    AccountLocked,
+    UnknownTransaction,
+    WrongClient,
+    AlreadyDisputed,
+    DisputeClosed,
+    DisputeNotOpen,
+    WithdrawalNotDisputable,
+    AccountFrozen,
+    AccountClosed,
+    AccountNotEmpty,
+    InvalidStatusChange,
+    NotAuthorised,
+    UnknownClient,
+    MissingDestination,
+    DestinationLocked,
+    DestinationClosed,
+    SelfTransfer,
+    ExceedsTransaction,
+    ExceedsDispute,
+}
+
+impl fmt::Display for EnumRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            EnumRejection::DuplicateTransaction => "duplicate transaction id",
+            EnumRejection::MissingAmount => "missing amount",
+            EnumRejection::InsufficientFunds => "insufficient funds",
+            EnumRejection::AccountLocked => "account locked",
+            EnumRejection::UnknownTransaction => "unknown transaction",
+            EnumRejection::WrongClient => "transaction of another client",
+            EnumRejection::AlreadyDisputed => "transaction already disputed",
+            EnumRejection::DisputeClosed => "dispute already closed",
+            EnumRejection::DisputeNotOpen => "no open dispute",
+            EnumRejection::WithdrawalNotDisputable => "withdrawals cannot be disputed",
+            EnumRejection::AccountFrozen => "account frozen",
+            EnumRejection::AccountClosed => "account closed",
+            EnumRejection::AccountNotEmpty => "account still has funds",
+            EnumRejection::InvalidStatusChange => "invalid account status change",
+            EnumRejection::NotAuthorised => "only accepted from the operator inputs",
+            EnumRejection::UnknownClient => "no account for the client",
+            EnumRejection::MissingDestination => "missing destination",
+            EnumRejection::DestinationLocked => "destination account locked",
+            EnumRejection::DestinationClosed => "destination account closed",
+            EnumRejection::SelfTransfer => "transfer to the same client",
+            EnumRejection::ExceedsTransaction => "amount above the disputable amount",
+            EnumRejection::ExceedsDispute => "amount above the disputed amount",
+        };
+        write!(f, "{}", reason)
+    }
 }
 
 impl Position {
@@ -23,151 +145,252 @@ impl Position {
             available: Default::default(),
             held: Default::default(),
             total: Default::default(),
-            locked: Default::default(),
+            status: Default::default(),
         }
     }
 
+    pub fn is_active(&self) -> bool {
+        self.status == EnumStatus::Active
+    }
+
     pub fn manage_transaction(
         self,
         transaction: &CSVParsed,
-        client_transactions: &[CSVParsed],
-    ) -> Position {
-        match (&transaction.r#type, self.locked) {
-            (_, true) => self.none(),
-            (EnumType::Deposit, _) => self.deposit(transaction),
-            (EnumType::Withdrawal, _) => self.withdrawal(transaction),
-            (EnumType::Dispute, _) => self.dispute(transaction, client_transactions),
-            (EnumType::Resolve, _) => self.resolve(transaction, client_transactions),
-            (EnumType::Chargeback, _) => self.chargeback(transaction, client_transactions),
+        store: &mut TransactionStore,
+        policy: &Policy,
+    ) -> Result<Position, EnumRejection> {
+        match (&transaction.r#type, self.status) {
+            (
+                r#type @ (EnumType::Unlock | EnumType::Freeze | EnumType::Close | EnumType::Reopen),
+                _,
+            ) => self.change_status(r#type),
+            // A dispute still open when the account was locked can still be settled
+            (r#type, EnumStatus::Locked)
+                if !matches!(r#type, EnumType::Resolve | EnumType::Chargeback) =>
+            {
+                Err(EnumRejection::AccountLocked)
+            }
+            (_, EnumStatus::Closed) => Err(EnumRejection::AccountClosed),
+            (EnumType::Withdrawal | EnumType::Transfer, EnumStatus::Frozen) => {
+                Err(EnumRejection::AccountFrozen)
+            }
+            (EnumType::Deposit, _) => self.deposit(transaction, store),
+            (EnumType::Withdrawal, _) => self.withdrawal(transaction, store),
+            (EnumType::Dispute, _) => self.dispute(transaction, store, policy),
+            (EnumType::Resolve, _) => self.resolve(transaction, store, policy),
+            (EnumType::Chargeback, _) => self.chargeback(transaction, store),
+            (EnumType::Transfer, _) => self.send(transaction),
         }
     }
 
-    fn deposit(self, transaction: &CSVParsed) -> Position {
+    // Side of a transfer that credits the destination, the source side goes through `manage_transaction`
+    pub fn receive(self, transaction: &CSVParsed) -> Result<Position, EnumRejection> {
+        let amount = transaction.amount.ok_or(EnumRejection::MissingAmount)?;
+        match self.status {
+            EnumStatus::Locked => return Err(EnumRejection::DestinationLocked),
+            EnumStatus::Closed => return Err(EnumRejection::DestinationClosed),
+            EnumStatus::Active | EnumStatus::Frozen => (),
+        }
+
+        Ok(Position {
+            available: Decimal::add(self.available, amount),
+            total: Decimal::add(self.total, amount),
+            ..self
+        })
+    }
+
+    // Gives back a transfer sent by this position when the destination refused it
+    pub fn refund(self, transaction: &CSVParsed) -> Position {
+        let amount = transaction.amount.unwrap_or_default();
         Position {
-            available: transaction.amount.map_or(self.available, |amount| {
-                Decimal::add(self.available, amount)
-            }),
-            total: transaction
-                .amount
-                .map_or(self.total, |amount| Decimal::add(self.total, amount)),
+            available: Decimal::add(self.available, amount),
+            total: Decimal::add(self.total, amount),
             ..self
         }
     }
 
-    fn withdrawal(self, transaction: &CSVParsed) -> Position {
-        if transaction
-            .amount
-            .map_or(false, |amount| amount < self.available)
-        {
-            Position {
-                available: transaction.amount.map_or(self.available, |amount| {
-                    Decimal::sub(self.available, amount)
-                }),
-                total: transaction
-                    .amount
-                    .map_or(self.total, |amount| Decimal::sub(self.total, amount)),
-                ..self
+    // Transfers are not stored, so they cannot be disputed
+    fn send(self, transaction: &CSVParsed) -> Result<Position, EnumRejection> {
+        let amount = transaction.amount.ok_or(EnumRejection::MissingAmount)?;
+        match transaction.destination {
+            None => return Err(EnumRejection::MissingDestination),
+            // The engine would credit and debit the same position, the parser already refuses it
+            Some(destination) if destination == self.client => {
+                return Err(EnumRejection::SelfTransfer)
             }
-        } else {
-            self
+            Some(_) => (),
+        }
+        if amount >= self.available {
+            return Err(EnumRejection::InsufficientFunds);
         }
+
+        Ok(Position {
+            available: Decimal::sub(self.available, amount),
+            total: Decimal::sub(self.total, amount),
+            ..self
+        })
     }
 
-    fn dispute(self, transaction: &CSVParsed, client_transactions: &[CSVParsed]) -> Position {
-        if let Some(value) = get_transaction_amount(transaction, client_transactions) {
-            Position {
-                available: Decimal::sub(self.available, value),
-                held: Decimal::add(self.held, value),
-                ..self
+    fn change_status(self, r#type: &EnumType) -> Result<Position, EnumRejection> {
+        let status = match (r#type, self.status) {
+            (EnumType::Unlock, EnumStatus::Locked | EnumStatus::Frozen) => EnumStatus::Active,
+            (EnumType::Freeze, EnumStatus::Active) => EnumStatus::Frozen,
+            (EnumType::Close, EnumStatus::Closed) => {
+                return Err(EnumRejection::InvalidStatusChange)
             }
-        } else {
-            self
-        }
+            (EnumType::Close, _) if !self.total.is_zero() || !self.held.is_zero() => {
+                return Err(EnumRejection::AccountNotEmpty)
+            }
+            (EnumType::Close, _) => EnumStatus::Closed,
+            (EnumType::Reopen, EnumStatus::Closed) => EnumStatus::Active,
+            (_, _) => return Err(EnumRejection::InvalidStatusChange),
+        };
+
+        Ok(Position { status, ..self })
     }
 
-    fn resolve(self, transaction: &CSVParsed, client_transactions: &[CSVParsed]) -> Position {
-        match (
-            has_a_dispute_transaction(transaction, client_transactions),
-            get_transaction_amount(transaction, client_transactions),
-        ) {
-            (true, Some(value)) => Position {
-                available: Decimal::add(self.available, value),
-                held: Decimal::sub(self.held, value),
-                ..self
-            },
-            _ => self,
-        }
+    fn deposit(
+        self,
+        transaction: &CSVParsed,
+        store: &mut TransactionStore,
+    ) -> Result<Position, EnumRejection> {
+        let amount = transaction.amount.ok_or(EnumRejection::MissingAmount)?;
+        store.insert(transaction);
+
+        Ok(Position {
+            available: Decimal::add(self.available, amount),
+            total: Decimal::add(self.total, amount),
+            ..self
+        })
     }
 
-    fn chargeback(self, transaction: &CSVParsed, client_transactions: &[CSVParsed]) -> Position {
-        match (
-            has_a_dispute_not_closed(transaction, client_transactions),
-            get_transaction_amount(transaction, client_transactions),
-        ) {
-            (true, Some(value)) => Position {
-                held: Decimal::sub(self.held, value),
-                total: Decimal::sub(self.total, value),
-                locked: true,
-                ..self
-            },
-            _ => self,
+    fn withdrawal(
+        self,
+        transaction: &CSVParsed,
+        store: &mut TransactionStore,
+    ) -> Result<Position, EnumRejection> {
+        let amount = transaction.amount.ok_or(EnumRejection::MissingAmount)?;
+        if amount >= self.available {
+            return Err(EnumRejection::InsufficientFunds);
         }
-    }
+        store.insert(transaction);
 
-    fn none(self) -> Position {
-        self
+        Ok(Position {
+            available: Decimal::sub(self.available, amount),
+            total: Decimal::sub(self.total, amount),
+            ..self
+        })
     }
-}
 
-fn get_transaction_amount(
-    transaction: &CSVParsed,
-    client_transactions: &[CSVParsed],
-) -> Option<Decimal> {
-    client_transactions
-        .iter()
-        .filter(|t| t.transaction_id == transaction.transaction_id && t.amount.is_some())
-        .last()
-        .and_then(|t| t.amount)
-}
+    fn dispute(
+        self,
+        transaction: &CSVParsed,
+        store: &mut TransactionStore,
+        policy: &Policy,
+    ) -> Result<Position, EnumRejection> {
+        let stored = store.get_mut(transaction)?;
 
-fn has_a_dispute_not_closed(transaction: &CSVParsed, client_transactions: &[CSVParsed]) -> bool {
-    has_a_dispute_transaction(transaction, client_transactions)
-        && client_transactions
-            .iter()
-            .filter(|t| {
-                t.transaction_id == transaction.transaction_id && t.r#type == EnumType::Resolve
-            })
-            .count()
-            != 1
-}
+        if stored.r#type == EnumType::Withdrawal
+            && policy.withdrawal_dispute == EnumWithdrawalDispute::Reject
+        {
+            return Err(EnumRejection::WithdrawalNotDisputable);
+        }
 
-fn has_a_dispute_transaction(transaction: &CSVParsed, client_transactions: &[CSVParsed]) -> bool {
-    client_transactions
-        .iter()
-        .filter(|t| t.transaction_id == transaction.transaction_id && t.r#type == EnumType::Dispute)
-        .count()
-        == 1
-}
+        match stored.state {
+            EnumState::Processed => (),
+            EnumState::Resolved if policy.allow_redispute => (),
+            EnumState::Disputed => return Err(EnumRejection::AlreadyDisputed),
+            EnumState::Resolved | EnumState::ChargedBack => {
+                return Err(EnumRejection::DisputeClosed)
+            }
+        }
+
+        let disputed = transaction.amount.unwrap_or(stored.amount);
+        if disputed > stored.amount {
+            return Err(EnumRejection::ExceedsTransaction);
+        }
 
-pub fn calculate_position_for_each_client(positions: Vec<CSVParsed>) -> Vec<Position> {
-    let clients: HashSet<u16> = positions.iter().map(|pos| pos.client).collect();
-
-    let mut client_positions: Vec<Position> = vec![];
-
-    for client in clients {
-        let client_transactions: Vec<CSVParsed> = positions
-            .clone()
-            .into_iter()
-            .filter(|pos| pos.client == client)
-            .collect();
-        let position: Position = client_transactions
-            .iter()
-            .fold(Position::new(client), |position, transaction| {
-                position.manage_transaction(transaction, &client_transactions)
+        // A disputed deposit is held out of the available funds, a disputed withdrawal is held on top of them
+        if stored.r#type == EnumType::Withdrawal {
+            stored.state = EnumState::Disputed;
+            stored.disputed = disputed;
+            stored.held = disputed;
+            return Ok(Position {
+                held: Decimal::add(self.held, disputed),
+                total: Decimal::add(self.total, disputed),
+                ..self
             });
+        }
 
-        client_positions.push(position);
+        let held = match policy.negative_balance {
+            _ if disputed <= self.available => disputed,
+            EnumNegativeBalance::Allow => disputed,
+            EnumNegativeBalance::Reject => return Err(EnumRejection::InsufficientFunds),
+            EnumNegativeBalance::HoldAvailable => self.available.max(Decimal::ZERO),
+        };
+        stored.state = EnumState::Disputed;
+        stored.disputed = disputed;
+        stored.held = held;
+
+        Ok(Position {
+            available: Decimal::sub(self.available, held),
+            held: Decimal::add(self.held, held),
+            ..self
+        })
+    }
+
+    fn resolve(
+        self,
+        transaction: &CSVParsed,
+        store: &mut TransactionStore,
+        policy: &Policy,
+    ) -> Result<Position, EnumRejection> {
+        let stored = store.get_mut(transaction)?;
+        let (settled, held) = stored.settle(transaction, EnumState::Resolved)?;
+        // What was resolved can only be disputed again with the policy
+        if !policy.allow_redispute {
+            stored.close(settled);
+        }
+
+        match stored.r#type {
+            EnumType::Withdrawal => Ok(Position {
+                held: Decimal::sub(self.held, held),
+                total: Decimal::sub(self.total, held),
+                ..self
+            }),
+            _ => Ok(Position {
+                available: Decimal::add(self.available, held),
+                held: Decimal::sub(self.held, held),
+                ..self
+            }),
+        }
     }
 
-    client_positions
+    fn chargeback(
+        self,
+        transaction: &CSVParsed,
+        store: &mut TransactionStore,
+    ) -> Result<Position, EnumRejection> {
+        let stored = store.get_mut(transaction)?;
+        let (settled, held) = stored.settle(transaction, EnumState::ChargedBack)?;
+        // What was charged back cannot be disputed again
+        stored.close(settled);
+
+        // The part of a deposit that could not be held leaves the available funds
+        match stored.r#type {
+            EnumType::Withdrawal => Ok(Position {
+                available: Decimal::add(self.available, held),
+                held: Decimal::sub(self.held, held),
+                status: EnumStatus::Locked,
+                ..self
+            }),
+            _ => Ok(Position {
+                available: Decimal::sub(self.available, settled - held),
+                held: Decimal::sub(self.held, held),
+                total: Decimal::sub(self.total, settled),
+                status: EnumStatus::Locked,
+                ..self
+            }),
+        }
+    }
 }
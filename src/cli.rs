@@ -0,0 +1,204 @@
+use clap::{Args, Parser, Subcommand};
+use rustling::audit::EnumAuditFormat;
+use rustling::calculation::{EnumNegativeBalance, EnumWithdrawalDispute};
+use rustling::parser::{EnumFormat, EnumInputFormat, EnumPrecision};
+use rustling::{EnumError, EnumOrder, Policy};
+
+#[cfg(test)]
+#[path = "test/cli_test.rs"]
+mod cli_test;
+
+// Without a subcommand the arguments are the ones of `process`, as before subcommands existed
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Compute client positions from a stream of transactions"
+)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub process: ProcessArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Process the transactions and write the final position of every client
+    Process(ProcessArgs),
+    /// Parse and check the input without processing it
+    Validate(InputArgs),
+    /// Process the transactions and write the outcome of every row
+    Audit(AuditArgs),
+    /// Process the transactions and write the history of one client
+    Replay(ReplayArgs),
+}
+
+#[derive(Args)]
+pub struct InputArgs {
+    /// Input files in processing order, `-` for stdin, globs are expanded
+    pub inputs: Vec<String>,
+
+    /// Input format: csv, jsonl or binary [default: from the file extension]
+    #[arg(long, value_name = "FORMAT", value_parser = parse::<EnumInputFormat>)]
+    pub input_format: Option<EnumInputFormat>,
+
+    /// Skip malformed rows and report them instead of stopping
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Round amounts to four decimal places instead of rejecting them
+    #[arg(long)]
+    pub round_amounts: bool,
+}
+
+#[derive(Args)]
+pub struct EngineArgs {
+    /// Allow a resolved transaction to be disputed again
+    #[arg(long)]
+    pub allow_redispute: bool,
+
+    /// Disputes on withdrawals: reject them, or reverse the withdrawal on a chargeback
+    #[arg(long, value_name = "POLICY", default_value = "reject", value_parser = parse::<EnumWithdrawalDispute>)]
+    pub withdrawal_disputes: EnumWithdrawalDispute,
+
+    /// Disputes of more than the available funds: allow, reject or hold-available
+    #[arg(long, value_name = "POLICY", default_value = "allow", value_parser = parse::<EnumNegativeBalance>)]
+    pub negative_balance: EnumNegativeBalance,
+
+    /// Start from the positions in this file, in the csv format written by `process`
+    #[arg(long, value_name = "FILE", conflicts_with = "resume")]
+    pub opening: Option<String>,
+
+    /// Start from an engine snapshot written by `--save`, disputes can refer to its transactions
+    #[arg(long, value_name = "SNAPSHOT")]
+    pub resume: Option<String>,
+
+    /// Operator inputs applied before the others, the only ones allowed to unlock, freeze, close or reopen an account
+    #[arg(long, value_name = "FILE")]
+    pub admin: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ProcessArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub engine: EngineArgs,
+
+    /// Write the positions to this file instead of stdout
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Output format: csv, json or jsonl
+    #[arg(long, default_value = "csv", value_parser = parse::<EnumFormat>)]
+    pub format: EnumFormat,
+
+    /// Order of the positions: client or appearance
+    #[arg(long, default_value = "client", value_parser = parse::<EnumOrder>)]
+    pub order: EnumOrder,
+
+    /// Also write the outcome of every row to this file
+    #[arg(long, value_name = "FILE")]
+    pub audit: Option<String>,
+
+    /// Format of the audit file: csv or jsonl
+    #[arg(long, value_name = "FORMAT", default_value = "csv", value_parser = parse::<EnumAuditFormat>)]
+    pub audit_format: EnumAuditFormat,
+
+    /// Write the clients with negative available funds or a dispute shortfall to this csv file
+    #[arg(long, value_name = "FILE")]
+    pub exposure: Option<String>,
+
+    /// Save the engine state to this file so that a later run can `--resume` from it
+    #[arg(long, value_name = "SNAPSHOT")]
+    pub save: Option<String>,
+
+    /// Journal the applied rows to this file, a run interrupted on the same journal resumes where it stopped
+    #[arg(long, value_name = "FILE")]
+    pub journal: Option<String>,
+
+    /// Rows between two checkpoints of the journal
+    #[arg(
+        long,
+        value_name = "ROWS",
+        default_value_t = 10000,
+        requires = "journal"
+    )]
+    pub checkpoint_every: usize,
+
+    /// Worker threads, each owning a shard of the clients, 0 for one per core
+    #[arg(long, value_name = "N", default_value_t = 1, conflicts_with_all = ["audit", "journal"])]
+    pub threads: usize,
+}
+
+#[derive(Args)]
+pub struct AuditArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub engine: EngineArgs,
+
+    /// Write the log to this file instead of stdout
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Output format: csv or jsonl
+    #[arg(long, default_value = "csv", value_parser = parse::<EnumAuditFormat>)]
+    pub format: EnumAuditFormat,
+
+    /// Only write the rejected rows
+    #[arg(long)]
+    pub rejected_only: bool,
+}
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub engine: EngineArgs,
+
+    /// Client whose history is written
+    #[arg(long)]
+    pub client: u16,
+
+    /// Write the history to this file instead of stdout
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Output format: csv or jsonl
+    #[arg(long, default_value = "csv", value_parser = parse::<EnumAuditFormat>)]
+    pub format: EnumAuditFormat,
+}
+
+fn parse<T>(value: &str) -> Result<T, EnumError>
+where
+    T: TryFrom<String, Error = EnumError>,
+{
+    value.to_string().try_into()
+}
+
+impl InputArgs {
+    pub fn precision(&self) -> EnumPrecision {
+        if self.round_amounts {
+            EnumPrecision::Round
+        } else {
+            EnumPrecision::Reject
+        }
+    }
+}
+
+impl From<&EngineArgs> for Policy {
+    fn from(args: &EngineArgs) -> Self {
+        Policy {
+            allow_redispute: args.allow_redispute,
+            withdrawal_dispute: args.withdrawal_disputes,
+            negative_balance: args.negative_balance,
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use crate::calculation::{EnumRejection, Policy, Position};
+use crate::parser::EnumType;
+use crate::snapshot::Snapshot;
+use crate::store::TransactionStore;
+use crate::{CSVParsed, EnumError};
+use rust_decimal::Decimal;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+#[cfg(test)]
+#[path = "test/engine_test.rs"]
+mod engine_test;
+
+#[cfg(test)]
+#[path = "test/engine_bench.rs"]
+pub(crate) mod engine_bench;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EnumOrder {
+    #[default]
+    Client,
+    Appearance,
+}
+
+impl TryFrom<String> for EnumOrder {
+    type Error = EnumError;
+
+    fn try_from(order: String) -> Result<Self, Self::Error> {
+        match order.as_str() {
+            "client" => Ok(Self::Client),
+            "appearance" => Ok(Self::Appearance),
+            _ => Err(EnumError::InvalidArgument(order)),
+        }
+    }
+}
+
+// A client whose disputes are not covered by its funds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exposure {
+    pub client: u16,
+    pub available: Decimal,
+    pub shortfall: Decimal,
+}
+
+/// Positions of every client and the transactions they can still dispute.
+///
+/// ```
+/// use rustling::{CSVParsed, Engine, EnumOrder, EnumRejection, EnumType};
+/// use rust_decimal::Decimal;
+///
+/// let transactions = [
+///     (EnumType::Deposit, 1, 1, Some(Decimal::new(10, 0))),
+///     (EnumType::Deposit, 2, 2, Some(Decimal::new(5, 0))),
+///     (EnumType::Dispute, 1, 1, None),
+///     (EnumType::Chargeback, 1, 1, None),
+///     (EnumType::Deposit, 1, 3, Some(Decimal::new(1, 0))),
+/// ];
+///
+/// let mut engine = Engine::default();
+/// let results: Vec<_> = transactions
+///     .into_iter()
+///     .map(|(r#type, client, transaction_id, amount)| {
+///         engine.process(&CSVParsed {
+///             r#type,
+///             client,
+///             transaction_id,
+///             amount,
+///             destination: None,
+///         })
+///     })
+///     .collect();
+///
+/// assert_eq!(Err(EnumRejection::AccountLocked), results[4]);
+/// let positions = engine.into_positions(EnumOrder::Client);
+/// assert!(!positions[0].is_active());
+/// assert_eq!(Decimal::new(5, 0), positions[1].total);
+/// ```
+#[derive(Default)]
+pub struct Engine {
+    positions: HashMap<u16, Position>,
+    appearance: Vec<u16>,
+    store: TransactionStore,
+    policy: Policy,
+}
+
+fn entry<'a>(
+    positions: &'a mut HashMap<u16, Position>,
+    appearance: &mut Vec<u16>,
+    client: u16,
+) -> &'a mut Position {
+    match positions.entry(client) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            appearance.push(client);
+            entry.insert(Position::new(client))
+        }
+    }
+}
+
+fn authorise(transaction: &CSVParsed) -> Result<(), EnumRejection> {
+    match transaction.r#type.is_admin() {
+        true => Err(EnumRejection::NotAuthorised),
+        false => Ok(()),
+    }
+}
+
+impl Engine {
+    pub fn with_policy(policy: Policy) -> Engine {
+        Engine {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot, policy: Policy) -> Engine {
+        let mut engine = Engine::with_policy(policy);
+        engine.restore(snapshot);
+        engine
+    }
+
+    // Replaces the whole state, the policy is kept
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.positions = snapshot
+            .positions
+            .iter()
+            .map(|position| (position.client, *position))
+            .collect();
+        self.appearance = snapshot
+            .positions
+            .iter()
+            .map(|position| position.client)
+            .collect();
+        self.store = snapshot.store;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.positions(), self.store.clone())
+    }
+
+    // Positions in order of appearance, without copying the transactions
+    pub fn positions(&self) -> Vec<Position> {
+        self.appearance
+            .iter()
+            .filter_map(|client| self.position(*client))
+            .collect()
+    }
+
+    pub(crate) fn store(&self) -> &TransactionStore {
+        &self.store
+    }
+
+    pub(crate) fn store_mut(&mut self) -> &mut TransactionStore {
+        &mut self.store
+    }
+
+    // Sets a position restored from a journal checkpoint, a new client comes after the others
+    pub(crate) fn update(&mut self, position: Position) {
+        *entry(&mut self.positions, &mut self.appearance, position.client) = position;
+    }
+
+    // Starts a client from a known position instead of an empty one
+    pub fn open(&mut self, position: Position) -> Result<(), EnumError> {
+        match self.positions.entry(position.client) {
+            Entry::Occupied(_) => Err(EnumError::DuplicateClient(position.client)),
+            Entry::Vacant(entry) => {
+                self.appearance.push(position.client);
+                entry.insert(position);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn process(&mut self, transaction: &CSVParsed) -> Result<(), EnumRejection> {
+        let registered = authorise(transaction).and_then(|_| self.store.register(transaction));
+        self.apply(transaction, registered)
+    }
+
+    // Rows of the operator inputs, the only ones that can change the status of an account
+    pub fn process_admin(&mut self, transaction: &CSVParsed) -> Result<(), EnumRejection> {
+        let registered = match transaction.r#type.is_admin() {
+            true => Ok(()),
+            false => self.store.register(transaction),
+        };
+        self.apply(transaction, registered)
+    }
+
+    // Applies a transaction whose id was registered beforehand, possibly by the router of a sharded run
+    pub(crate) fn apply(
+        &mut self,
+        transaction: &CSVParsed,
+        registered: Result<(), EnumRejection>,
+    ) -> Result<(), EnumRejection> {
+        // The status of an account is only changed once it exists
+        if transaction.r#type.is_admin() && !self.positions.contains_key(&transaction.client) {
+            return registered.and(Err(EnumRejection::UnknownClient));
+        }
+
+        let source = *entry(
+            &mut self.positions,
+            &mut self.appearance,
+            transaction.client,
+        );
+
+        registered?;
+
+        // Both sides are computed before either is stored, so a transfer is applied whole or not at all,
+        // and a destination without an account only gets one when it is credited
+        let source = source.manage_transaction(transaction, &mut self.store, &self.policy)?;
+        if let (EnumType::Transfer, Some(client)) = (&transaction.r#type, transaction.destination) {
+            let destination = self.credit(client, transaction)?;
+            *entry(&mut self.positions, &mut self.appearance, client) = destination;
+        }
+        self.positions.insert(source.client, source);
+
+        Ok(())
+    }
+
+    // Source side of a transfer to a client of another shard
+    pub(crate) fn send(
+        &mut self,
+        transaction: &CSVParsed,
+        registered: Result<(), EnumRejection>,
+    ) -> Result<(), EnumRejection> {
+        let position = entry(
+            &mut self.positions,
+            &mut self.appearance,
+            transaction.client,
+        );
+
+        registered?;
+
+        *position = position.manage_transaction(transaction, &mut self.store, &self.policy)?;
+        Ok(())
+    }
+
+    // Destination side of a transfer from a client of another shard
+    pub(crate) fn receive(&mut self, transaction: &CSVParsed) -> Result<(), EnumRejection> {
+        let client = transaction
+            .destination
+            .ok_or(EnumRejection::MissingDestination)?;
+        let destination = self.credit(client, transaction)?;
+
+        *entry(&mut self.positions, &mut self.appearance, client) = destination;
+        Ok(())
+    }
+
+    fn credit(&self, client: u16, transaction: &CSVParsed) -> Result<Position, EnumRejection> {
+        self.positions
+            .get(&client)
+            .copied()
+            .unwrap_or_else(|| Position::new(client))
+            .receive(transaction)
+    }
+
+    pub(crate) fn refund(&mut self, transaction: &CSVParsed) {
+        if let Some(position) = self.positions.get_mut(&transaction.client) {
+            *position = position.refund(transaction);
+        }
+    }
+
+    // Only registers the id and the appearance of the client, the position is left to the shard
+    pub(crate) fn route(&mut self, transaction: &CSVParsed) -> Result<(), EnumRejection> {
+        authorise(transaction)?;
+        entry(
+            &mut self.positions,
+            &mut self.appearance,
+            transaction.client,
+        );
+        self.store.register(transaction)
+    }
+
+    // The destination of a transfer appears once the shard credited it
+    pub(crate) fn credited(&mut self, client: u16) {
+        entry(&mut self.positions, &mut self.appearance, client);
+    }
+
+    // Shards the clients by `client % shards`, `self` becomes the router of the shards
+    pub(crate) fn split(&mut self, shards: usize) -> Vec<Engine> {
+        let mut split: Vec<Engine> = self
+            .store
+            .split(shards)
+            .into_iter()
+            .map(|store| Engine {
+                store,
+                policy: self.policy.clone(),
+                ..Default::default()
+            })
+            .collect();
+
+        for client in &self.appearance {
+            if let Some(position) = self.positions.get_mut(client) {
+                let shard = &mut split[*client as usize % shards];
+                shard.appearance.push(*client);
+                shard.positions.insert(*client, *position);
+                *position = Position::new(*client);
+            }
+        }
+        split
+    }
+
+    // The router keeps the order of appearance, the shards have the positions
+    pub(crate) fn merge(&mut self, shards: Vec<Engine>) {
+        for shard in shards {
+            self.store.merge(shard.store);
+            self.positions.extend(shard.positions);
+        }
+    }
+
+    pub fn position(&self, client: u16) -> Option<Position> {
+        self.positions.get(&client).copied()
+    }
+
+    pub fn exposures(&self) -> Vec<Exposure> {
+        let shortfalls = self.store.shortfalls();
+        let mut exposures: Vec<_> = self
+            .positions
+            .values()
+            .map(|position| Exposure {
+                client: position.client,
+                available: position.available,
+                shortfall: shortfalls
+                    .get(&position.client)
+                    .copied()
+                    .unwrap_or_default(),
+            })
+            .filter(|exposure| {
+                exposure.available < Decimal::ZERO || exposure.shortfall > Decimal::ZERO
+            })
+            .collect();
+        exposures.sort_unstable_by_key(|exposure| exposure.client);
+        exposures
+    }
+
+    pub fn into_positions(mut self, order: EnumOrder) -> Vec<Position> {
+        if order == EnumOrder::Client {
+            self.appearance.sort_unstable();
+        }
+
+        self.appearance
+            .iter()
+            .filter_map(|client| self.positions.remove(client))
+            .collect()
+    }
+}
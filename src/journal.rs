@@ -0,0 +1,262 @@
+use crate::calculation::Position;
+use crate::engine::Engine;
+use crate::parser::{CSVParsed, STDIN};
+use crate::snapshot::{self, Snapshot};
+use crate::store::{StoredTransaction, TransactionStore};
+use crate::EnumError;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+#[cfg(test)]
+#[path = "test/journal_test.rs"]
+mod journal_test;
+
+// One line per row given to the engine, `row` counts every row of the inputs, malformed ones included
+#[derive(Serialize, Deserialize)]
+struct Entry<'a> {
+    row: usize,
+    #[serde(flatten)]
+    transaction: Cow<'a, CSVParsed>,
+}
+
+// An input of the run, a checkpoint is only resumed with the same inputs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Input {
+    pub name: String,
+    // None for stdin, which cannot be read again
+    pub size: Option<u64>,
+}
+
+impl Input {
+    pub fn new(file_name: &str) -> Input {
+        Input {
+            name: file_name.to_string(),
+            size: match file_name {
+                STDIN => None,
+                _ => fs::metadata(file_name).map(|metadata| metadata.len()).ok(),
+            },
+        }
+    }
+}
+
+// First line of the checkpoint file, the whole engine state when the journal was opened
+#[derive(Serialize, Deserialize)]
+struct Checkpoint<'a> {
+    version: u32,
+    row: usize,
+    inputs: Cow<'a, [Input]>,
+    positions: Vec<Position>,
+    store: Cow<'a, TransactionStore>,
+}
+
+// One line per commit after it, with what the rows since the previous commit touched
+#[derive(Serialize, Deserialize)]
+struct Changes {
+    row: usize,
+    positions: Vec<Position>,
+    transactions: Vec<(u32, u16, Option<StoredTransaction>)>,
+}
+
+// Complete lines of a file, a torn last line was never committed
+fn lines<R: BufRead>(mut reader: R) -> impl Iterator<Item = Result<Vec<u8>, EnumError>> {
+    std::iter::from_fn(move || {
+        let mut line = vec![];
+        match reader.read_until(b'\n', &mut line) {
+            Err(err) => Some(Err(EnumError::CannotReadInput(err.to_string()))),
+            Ok(_) if line.last() == Some(&b'\n') => Some(Ok(line)),
+            Ok(_) => None,
+        }
+    })
+}
+
+// Append only log of the rows applied by a run, with the changes to the engine state checkpointed every
+// few rows. After a crash the checkpoint is restored and the rows journaled after it are applied again.
+pub struct Journal {
+    writer: BufWriter<File>,
+    changes: BufWriter<File>,
+    file_name: String,
+    checkpoint: String,
+    every: usize,
+    pending: usize,
+    row: usize,
+    // Clients and transaction ids of the rows since the last commit
+    clients: Vec<u16>,
+    transactions: Vec<u32>,
+}
+
+impl Journal {
+    // Recovers the engine from an earlier run of the same journal over the same inputs, `engine` is only
+    // used on a fresh start
+    pub fn open(
+        file_name: &str,
+        inputs: Vec<Input>,
+        every: usize,
+        engine: &mut Engine,
+    ) -> Result<Journal, EnumError> {
+        let checkpoint = format!("{}.checkpoint", file_name);
+
+        let mut row = 0;
+        if Path::new(&checkpoint).exists() {
+            let mut reader =
+                BufReader::new(File::open(&checkpoint).map_err(|_| EnumError::CannotOpenCsv)?);
+            // Written whole by a rename, it may miss its line end
+            let mut base = vec![];
+            reader
+                .read_until(b'\n', &mut base)
+                .map_err(|err| EnumError::CannotReadInput(err.to_string()))?;
+            let base: Checkpoint = snapshot::load(base.as_slice())
+                .map_err(|err| EnumError::InFile(checkpoint.clone(), Box::new(err)))?;
+            if *base.inputs != *inputs || inputs.iter().any(|input| input.size.is_none()) {
+                return Err(EnumError::JournalMismatch(file_name.to_string()));
+            }
+            engine.restore(Snapshot::new(base.positions, base.store.into_owned()));
+            row = base.row;
+
+            for line in lines(reader) {
+                let Ok(changes) = serde_json::from_slice::<Changes>(&line?) else {
+                    break;
+                };
+                for position in changes.positions {
+                    engine.update(position);
+                }
+                for (transaction_id, client, stored) in changes.transactions {
+                    engine.store_mut().put(transaction_id, client, stored);
+                }
+                row = changes.row;
+            }
+
+            if let Ok(file) = File::open(file_name) {
+                for line in lines(BufReader::new(file)) {
+                    let Ok(entry) = serde_json::from_slice::<Entry>(&line?) else {
+                        break;
+                    };
+                    if entry.row > row {
+                        let _ = engine.process(&entry.transaction);
+                        row = entry.row;
+                    }
+                }
+            }
+        }
+
+        // The recovered state is the new base, so neither file keeps what came before it
+        snapshot::save(
+            &checkpoint,
+            &Checkpoint {
+                version: snapshot::VERSION,
+                row,
+                inputs: Cow::Borrowed(&inputs),
+                positions: engine.positions(),
+                store: Cow::Borrowed(engine.store()),
+            },
+        )?;
+        let mut changes = BufWriter::new(
+            OpenOptions::new()
+                .append(true)
+                .open(&checkpoint)
+                .map_err(|_| EnumError::CannotWriteCsv)?,
+        );
+        changes
+            .write_all(b"\n")
+            .and_then(|_| changes.flush())
+            .map_err(|_| EnumError::CannotWriteLine)?;
+        let file = File::create(file_name).map_err(|_| EnumError::CannotWriteCsv)?;
+
+        Ok(Journal {
+            writer: BufWriter::new(file),
+            changes,
+            file_name: file_name.to_string(),
+            checkpoint,
+            every,
+            pending: 0,
+            row,
+            clients: vec![],
+            transactions: vec![],
+        })
+    }
+
+    // Rows of the inputs already applied by an earlier run
+    pub fn applied(&self) -> usize {
+        self.row
+    }
+
+    pub fn record(
+        &mut self,
+        row: usize,
+        transaction: &CSVParsed,
+        engine: &Engine,
+    ) -> Result<(), EnumError> {
+        serde_json::to_writer(
+            &mut self.writer,
+            &Entry {
+                row,
+                transaction: Cow::Borrowed(transaction),
+            },
+        )
+        .map_err(|_| EnumError::CannotWriteLine)?;
+        self.writer
+            .write_all(b"\n")
+            .map_err(|_| EnumError::CannotWriteLine)?;
+
+        self.clients.push(transaction.client);
+        self.clients.extend(transaction.destination);
+        self.transactions.push(transaction.transaction_id);
+
+        self.row = row;
+        self.pending += 1;
+        if self.pending >= self.every {
+            self.commit(engine)?;
+        }
+        Ok(())
+    }
+
+    // Appends the positions and transactions touched since the last commit, in order of appearance
+    pub fn commit(&mut self, engine: &Engine) -> Result<(), EnumError> {
+        self.pending = 0;
+        self.writer
+            .flush()
+            .map_err(|_| EnumError::CannotWriteLine)?;
+
+        let mut clients = HashSet::new();
+        let mut transactions = HashSet::new();
+        let changes = Changes {
+            row: self.row,
+            positions: self
+                .clients
+                .drain(..)
+                .filter(|client| clients.insert(*client))
+                .filter_map(|client| engine.position(client))
+                .collect(),
+            transactions: self
+                .transactions
+                .drain(..)
+                .filter(|transaction_id| transactions.insert(*transaction_id))
+                .filter_map(|transaction_id| {
+                    engine
+                        .store()
+                        .get(transaction_id)
+                        .map(|(client, stored)| (transaction_id, client, stored.cloned()))
+                })
+                .collect(),
+        };
+
+        serde_json::to_writer(&mut self.changes, &changes)
+            .map_err(|_| EnumError::CannotWriteLine)?;
+        self.changes
+            .write_all(b"\n")
+            .and_then(|_| self.changes.flush())
+            .map_err(|_| EnumError::CannotWriteLine)
+    }
+
+    // Once the run is over and its output written there is nothing left to resume
+    pub fn finish(self) -> Result<(), EnumError> {
+        drop(self.writer);
+        drop(self.changes);
+        fs::remove_file(&self.checkpoint)
+            .and_then(|_| fs::remove_file(&self.file_name))
+            .map_err(|_| EnumError::CannotWriteLine)
+    }
+}
@@ -0,0 +1,206 @@
+//! Engine of the `rustling` cli, for services that process transactions themselves.
+//!
+//! Rows are parsed into [`CSVParsed`] by the readers of [`parser`], applied by an [`Engine`] and the
+//! final positions written back with [`parser::write_out_positions`].
+//!
+//! ```
+//! use rustling::parser::{read_transactions, EnumInputFormat, EnumPrecision};
+//! use rustling::{Engine, EnumOrder, EnumRejection};
+//!
+//! let input = "type,client,tx,amount
+//! deposit,1,1,2.0
+//! withdrawal,1,2,5.0
+//! dispute,1,1,
+//! ";
+//!
+//! let mut engine = Engine::default();
+//! for transaction in read_transactions(input.as_bytes(), EnumInputFormat::Csv, EnumPrecision::Reject) {
+//!     let transaction = transaction?;
+//!     if let Err(rejection) = engine.process(&transaction) {
+//!         assert_eq!(EnumRejection::InsufficientFunds, rejection);
+//!     }
+//! }
+//!
+//! let positions = engine.into_positions(EnumOrder::Client);
+//! assert_eq!("2.0", positions[0].held.to_string());
+//! assert_eq!("0.0", positions[0].available.to_string());
+//! # Ok::<(), rustling::EnumError>(())
+//! ```
+
+#[cfg(feature = "async")]
+pub mod async_engine;
+pub mod audit;
+pub mod binary;
+pub mod calculation;
+pub mod engine;
+pub mod journal;
+pub mod parallel;
+pub mod parser;
+pub mod snapshot;
+pub mod store;
+
+pub use crate::calculation::{EnumRejection, Policy, Position};
+pub use crate::engine::{Engine, EnumOrder};
+pub use crate::parser::{CSVParsed, EnumType};
+use std::{error, fmt};
+
+#[derive(Debug)]
+pub enum EnumError {
+    NoInputFile,
+    InvalidArgument(String),
+    InvalidCSV {
+        line: u64,
+        message: String,
+    },
+    InvalidType {
+        line: u64,
+        value: String,
+    },
+    CannotConvert {
+        line: u64,
+        column: String,
+        value: String,
+    },
+    InvalidAmount {
+        line: u64,
+        value: String,
+        reason: String,
+    },
+    InvalidDestination {
+        line: u64,
+        value: String,
+        reason: String,
+    },
+    InvalidJson {
+        line: u64,
+        message: String,
+    },
+    InvalidBinary {
+        record: u64,
+        message: String,
+    },
+    CannotReadInput(String),
+    CannotWriteCsv,
+    CannotWriteLine,
+    CannotOpenCsv,
+    FileNotPresent(String),
+    InFile(String, Box<EnumError>),
+    MalformedRows(usize),
+    DuplicateClient(u16),
+    InvalidSnapshot(String),
+    JournalMismatch(String),
+}
+
+impl EnumError {
+    // Follows the sysexits.h convention
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            EnumError::NoInputFile
+            | EnumError::InvalidArgument(_)
+            | EnumError::JournalMismatch(_) => 64,
+            EnumError::InvalidCSV { .. }
+            | EnumError::InvalidType { .. }
+            | EnumError::CannotConvert { .. }
+            | EnumError::InvalidAmount { .. }
+            | EnumError::InvalidDestination { .. }
+            | EnumError::InvalidJson { .. }
+            | EnumError::InvalidBinary { .. }
+            | EnumError::MalformedRows(_)
+            | EnumError::DuplicateClient(_)
+            | EnumError::InvalidSnapshot(_) => 65,
+            EnumError::CannotOpenCsv | EnumError::FileNotPresent(_) => 66,
+            EnumError::CannotWriteCsv => 73,
+            EnumError::CannotReadInput(_) | EnumError::CannotWriteLine => 74,
+            EnumError::InFile(_, err) => err.exit_code(),
+        }
+    }
+
+    // A malformed row can be skipped, anything else stops the run
+    pub fn is_malformed_row(&self) -> bool {
+        match self {
+            EnumError::InvalidCSV { .. }
+            | EnumError::InvalidType { .. }
+            | EnumError::CannotConvert { .. }
+            | EnumError::InvalidAmount { .. }
+            | EnumError::InvalidDestination { .. }
+            | EnumError::InvalidJson { .. } => true,
+            EnumError::InFile(_, err) => err.is_malformed_row(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for EnumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnumError::NoInputFile => write!(f, "no input file given"),
+            EnumError::InvalidArgument(arg) => write!(f, "invalid argument '{}'", arg),
+            EnumError::InvalidCSV { line, message } => {
+                write!(f, "line {}: invalid csv: {}", line, message)
+            }
+            EnumError::InvalidType { line, value } => {
+                write!(f, "line {}: unknown transaction type '{}'", line, value)
+            }
+            EnumError::CannotConvert {
+                line,
+                column,
+                value,
+            } => write!(f, "line {}: cannot convert {} '{}'", line, column, value),
+            EnumError::InvalidAmount {
+                line,
+                value,
+                reason,
+            } => write!(f, "line {}: invalid amount '{}': {}", line, value, reason),
+            EnumError::InvalidDestination {
+                line,
+                value,
+                reason,
+            } => write!(
+                f,
+                "line {}: invalid destination '{}': {}",
+                line, value, reason
+            ),
+            EnumError::InvalidJson { line, message } => {
+                write!(f, "line {}: invalid json: {}", line, message)
+            }
+            EnumError::InvalidBinary { record, message } => {
+                write!(f, "record {}: invalid binary record: {}", record, message)
+            }
+            EnumError::CannotReadInput(message) => write!(f, "cannot read the input: {}", message),
+            EnumError::CannotWriteCsv => write!(f, "cannot create the output file"),
+            EnumError::CannotWriteLine => write!(f, "cannot write the output"),
+            EnumError::CannotOpenCsv => write!(f, "cannot open the csv file"),
+            EnumError::FileNotPresent(file) => write!(f, "cannot open file '{}'", file),
+            EnumError::InFile(file, err) => write!(f, "{}: {}", file, err),
+            EnumError::MalformedRows(count) => write!(f, "{} malformed rows", count),
+            EnumError::DuplicateClient(client) => write!(f, "client {} appears twice", client),
+            EnumError::InvalidSnapshot(message) => write!(f, "invalid snapshot: {}", message),
+            EnumError::JournalMismatch(file) => write!(
+                f,
+                "journal '{}' was written for other inputs, remove it to start over",
+                file
+            ),
+        }
+    }
+}
+
+impl error::Error for EnumError {}
+
+impl From<csv::Error> for EnumError {
+    fn from(err: csv::Error) -> Self {
+        if let csv::ErrorKind::Io(err) = err.kind() {
+            return EnumError::CannotReadInput(err.to_string());
+        }
+
+        let line = err.position().map_or(0, |position| position.line());
+        let message = match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+            csv::ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => format!("expected {} fields, found {}", expected_len, len),
+            _ => err.to_string(),
+        };
+
+        EnumError::InvalidCSV { line, message }
+    }
+}
@@ -1,32 +1,302 @@
-mod calculation;
-mod parser;
+mod cli;
 
-use crate::parser::*;
-use std::env;
+use crate::cli::{AuditArgs, Cli, Command, EngineArgs, InputArgs, ProcessArgs, ReplayArgs};
+use clap::Parser;
+use rustling::audit::AuditLog;
+use rustling::journal::{Input, Journal};
+use rustling::parser::{self, CSVParsed, EnumPrecision};
+use rustling::{parallel, snapshot, Engine, EnumError, EnumRejection};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
 
-#[derive(Debug)]
-pub enum EnumError {
-    NoInputFile,
-    InvalidCSV,
-    InvalidType,
-    CannotConvert(String),
-    CannotWriteCsv,
-    CannotWriteLine,
-    CannotOpenCsv,
-    FileNotPresent,
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            let _ = err.print();
+            return if err.use_stderr() {
+                ExitCode::from(EnumError::InvalidArgument(String::new()).exit_code())
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), EnumError> {
+    match cli.command {
+        None => process(cli.process),
+        Some(Command::Process(args)) => process(args),
+        Some(Command::Validate(args)) => validate(args),
+        Some(Command::Audit(args)) => audit(args),
+        Some(Command::Replay(args)) => replay(args),
+    }
+}
+
+fn create_output(output: Option<String>) -> Result<Box<dyn Write>, EnumError> {
+    match output {
+        Some(file_name) => Ok(Box::new(BufWriter::new(
+            File::create(file_name).map_err(|_| EnumError::CannotWriteCsv)?,
+        ))),
+        None => Ok(Box::new(io::stdout().lock())),
+    }
 }
 
-fn main() -> Result<(), EnumError> {
-    let file_input = env::args()
-        .into_iter()
-        .nth(1)
-        .ok_or(EnumError::NoInputFile)?;
+fn create_engine(args: &EngineArgs) -> Result<Engine, EnumError> {
+    let mut engine = match &args.resume {
+        Some(file_name) => Engine::from_snapshot(
+            snapshot::load(parser::open_csv(file_name.clone())?)
+                .map_err(|err| EnumError::InFile(file_name.clone(), Box::new(err)))?,
+            args.into(),
+        ),
+        None => Engine::with_policy(args.into()),
+    };
 
-    let csv_parsed = parser::read_csv(file_input)?;
+    if let Some(file_name) = &args.opening {
+        for position in parser::read_positions(parser::open_csv(file_name.clone())?) {
+            position
+                .and_then(|position| engine.open(position))
+                .map_err(|err| EnumError::InFile(file_name.clone(), Box::new(err)))?;
+        }
+    }
 
-    let calculated_positions = calculation::calculate_position_for_each_client(csv_parsed);
+    for transaction in parser::read_inputs(args.admin.clone(), None, EnumPrecision::Reject) {
+        let transaction = transaction?;
+        if let Err(rejection) = engine.process_admin(&transaction) {
+            report_rejection(&transaction, &rejection);
+        }
+    }
 
-    parser::write_out_positions(calculated_positions.into_iter().map(Into::into).collect());
+    Ok(engine)
+}
+
+fn report_skipped(skipped: &[EnumError]) {
+    if !skipped.is_empty() {
+        eprintln!("skipped {} malformed rows:", skipped.len());
+        for err in skipped {
+            eprintln!("  {}", err);
+        }
+    }
+}
 
+// Rows of the inputs after the first `skip` with their row number, malformed rows are skipped when lenient
+fn read_rows(
+    input: InputArgs,
+    skip: usize,
+    skipped: &mut Vec<EnumError>,
+) -> Result<impl Iterator<Item = Result<(usize, CSVParsed), EnumError>> + '_, EnumError> {
+    if input.inputs.is_empty() {
+        return Err(EnumError::NoInputFile);
+    }
+
+    let lenient = input.lenient;
+    let precision = input.precision();
+    Ok(parser::read_inputs(
+        parser::expand_inputs(input.inputs)?,
+        input.input_format,
+        precision,
+    )
+    .enumerate()
+    .skip(skip)
+    .filter_map(move |(row, transaction)| match transaction {
+        Ok(transaction) => Some(Ok((row + 1, transaction))),
+        Err(err) if lenient && err.is_malformed_row() => {
+            skipped.push(err);
+            None
+        }
+        Err(err) => Some(Err(err)),
+    }))
+}
+
+// Feeds every row of the inputs after the first `skip` to the engine, a malformed row stops the run unless lenient
+fn process_inputs<F>(
+    input: InputArgs,
+    engine: &mut Engine,
+    skip: usize,
+    mut on_row: F,
+) -> Result<(), EnumError>
+where
+    F: FnMut(&Engine, usize, &CSVParsed, Result<(), EnumRejection>) -> Result<(), EnumError>,
+{
+    let mut skipped = vec![];
+    for row in read_rows(input, skip, &mut skipped)? {
+        let (row, transaction) = row?;
+        let result = engine.process(&transaction);
+        on_row(engine, row, &transaction, result)?;
+    }
+
+    report_skipped(&skipped);
     Ok(())
 }
+
+fn report_rejection(transaction: &CSVParsed, rejection: &EnumRejection) {
+    eprintln!(
+        "{} {} of client {} rejected: {}",
+        transaction.r#type, transaction.transaction_id, transaction.client, rejection
+    );
+}
+
+// Same as `process_inputs` on client shards, the rejections are reported once every row is applied
+fn process_in_parallel(
+    input: InputArgs,
+    engine: &mut Engine,
+    threads: usize,
+) -> Result<(), EnumError> {
+    let mut skipped = vec![];
+    let rejected = parallel::process(engine, threads, read_rows(input, 0, &mut skipped)?)?;
+
+    for rejected in rejected {
+        report_rejection(&rejected.transaction, &rejected.rejection);
+    }
+    report_skipped(&skipped);
+    Ok(())
+}
+
+fn process(args: ProcessArgs) -> Result<(), EnumError> {
+    let mut audit = match args.audit {
+        Some(file_name) => Some(AuditLog::new(
+            BufWriter::new(File::create(file_name).map_err(|_| EnumError::CannotWriteCsv)?),
+            args.audit_format,
+        )),
+        None => None,
+    };
+
+    let mut engine = create_engine(&args.engine)?;
+    let mut journal = match &args.journal {
+        Some(file_name) => Some(Journal::open(
+            file_name,
+            parser::expand_inputs(args.input.inputs.clone())?
+                .iter()
+                .map(|file_name| Input::new(file_name))
+                .collect(),
+            args.checkpoint_every,
+            &mut engine,
+        )?),
+        None => None,
+    };
+    let skip = journal.as_ref().map_or(0, Journal::applied);
+
+    match parallel::threads(args.threads) {
+        1 => process_inputs(
+            args.input,
+            &mut engine,
+            skip,
+            |engine, row, transaction, result| {
+                if let Some(journal) = &mut journal {
+                    journal.record(row, transaction, engine)?;
+                }
+                match (&mut audit, &result) {
+                    (Some(audit), _) => audit.record(transaction, &result)?,
+                    (None, Err(rejection)) => report_rejection(transaction, rejection),
+                    (None, Ok(())) => (),
+                }
+                Ok(())
+            },
+        )?,
+        threads => process_in_parallel(args.input, &mut engine, threads)?,
+    }
+
+    if let Some(audit) = &mut audit {
+        audit.flush()?;
+    }
+    if let Some(journal) = &mut journal {
+        journal.commit(&engine)?;
+    }
+
+    if let Some(file_name) = args.exposure {
+        parser::write_exposures(create_output(Some(file_name))?, engine.exposures())?;
+    }
+    if let Some(file_name) = args.save {
+        snapshot::save(&file_name, &engine.snapshot())?;
+    }
+
+    parser::write_out_positions(
+        create_output(args.output)?,
+        args.format,
+        engine
+            .into_positions(args.order)
+            .into_iter()
+            .map(Into::into),
+    )?;
+
+    match journal {
+        Some(journal) => journal.finish(),
+        None => Ok(()),
+    }
+}
+
+fn validate(input: InputArgs) -> Result<(), EnumError> {
+    if input.inputs.is_empty() {
+        return Err(EnumError::NoInputFile);
+    }
+
+    let mut rows = 0;
+    let mut malformed = 0;
+    let precision = input.precision();
+    for transaction in parser::read_inputs(
+        parser::expand_inputs(input.inputs)?,
+        input.input_format,
+        precision,
+    ) {
+        rows += 1;
+        match transaction {
+            Ok(_) => (),
+            Err(err) if err.is_malformed_row() => {
+                eprintln!("{}", err);
+                malformed += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    eprintln!("{} rows, {} malformed", rows, malformed);
+    match malformed {
+        0 => Ok(()),
+        count => Err(EnumError::MalformedRows(count)),
+    }
+}
+
+fn audit(args: AuditArgs) -> Result<(), EnumError> {
+    let mut audit = AuditLog::new(create_output(args.output)?, args.format);
+
+    let mut engine = create_engine(&args.engine)?;
+    process_inputs(args.input, &mut engine, 0, |_, _, transaction, result| {
+        if args.rejected_only && result.is_ok() {
+            return Ok(());
+        }
+        audit.record(transaction, &result)
+    })?;
+
+    audit.flush()
+}
+
+fn replay(args: ReplayArgs) -> Result<(), EnumError> {
+    let mut replay = AuditLog::new(create_output(args.output)?, args.format);
+
+    let mut engine = create_engine(&args.engine)?;
+    process_inputs(
+        args.input,
+        &mut engine,
+        0,
+        |engine, _, transaction, result| {
+            // Transfers received by the client are part of its history too
+            let involved =
+                transaction.client == args.client || transaction.destination == Some(args.client);
+            match engine.position(args.client) {
+                Some(position) if involved => replay.replay(transaction, &result, position),
+                _ => Ok(()),
+            }
+        },
+    )?;
+
+    replay.flush()
+}
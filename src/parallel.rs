@@ -0,0 +1,181 @@
+use crate::calculation::EnumRejection;
+use crate::engine::Engine;
+use crate::parser::{CSVParsed, EnumType};
+use crate::EnumError;
+use std::panic;
+use std::sync::mpsc;
+use std::thread;
+
+#[cfg(test)]
+#[path = "test/parallel_test.rs"]
+mod parallel_test;
+
+#[cfg(test)]
+#[path = "test/parallel_bench.rs"]
+mod parallel_bench;
+
+// Rows are sent to the workers in batches, one message per row costs more than applying it
+const BATCH: usize = 1024;
+
+type Batch = Vec<(usize, CSVParsed, Result<(), EnumRejection>)>;
+
+// A transfer between two shards is run by the router one leg at a time, waiting for each reply
+enum Message {
+    Rows(Batch),
+    Send(
+        CSVParsed,
+        Result<(), EnumRejection>,
+        mpsc::Sender<Result<(), EnumRejection>>,
+    ),
+    Receive(CSVParsed, mpsc::Sender<Result<(), EnumRejection>>),
+    Refund(CSVParsed),
+}
+
+#[derive(Debug)]
+pub struct Rejected {
+    pub row: usize,
+    pub transaction: CSVParsed,
+    pub rejection: EnumRejection,
+}
+
+pub fn threads(requested: usize) -> usize {
+    match requested {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    }
+}
+
+// Each worker owns the clients with `client % threads` equal to its index and gets their rows in
+// order. The calling thread is the router: it reads the rows and checks the transaction ids, which
+// are unique across every client. A transfer waits for the shards of both clients to catch up, then
+// debits the source and credits the destination, refunding the source when the credit is rejected,
+// so the router knows when the destination appears. Rejections come back ordered by row.
+pub fn process<I>(engine: &mut Engine, threads: usize, rows: I) -> Result<Vec<Rejected>, EnumError>
+where
+    I: IntoIterator<Item = Result<(usize, CSVParsed), EnumError>>,
+{
+    let shards = engine.split(threads);
+
+    let (shards, routed, mut rejected) = thread::scope(|scope| {
+        let (senders, workers): (Vec<_>, Vec<_>) = shards
+            .into_iter()
+            .map(|mut shard| {
+                let (sender, receiver) = mpsc::sync_channel::<Message>(16);
+                let worker = scope.spawn(move || {
+                    let mut rejected = vec![];
+                    for message in receiver {
+                        match message {
+                            Message::Rows(batch) => {
+                                for (row, transaction, registered) in batch {
+                                    if let Err(rejection) = shard.apply(&transaction, registered) {
+                                        rejected.push(Rejected {
+                                            row,
+                                            transaction,
+                                            rejection,
+                                        });
+                                    }
+                                }
+                            }
+                            Message::Send(transaction, registered, reply) => {
+                                let _ = reply.send(shard.send(&transaction, registered));
+                            }
+                            Message::Receive(transaction, reply) => {
+                                let _ = reply.send(shard.receive(&transaction));
+                            }
+                            Message::Refund(transaction) => shard.refund(&transaction),
+                        }
+                    }
+                    (shard, rejected)
+                });
+                (sender, worker)
+            })
+            .unzip();
+
+        let mut batches: Vec<Batch> = (0..threads).map(|_| Vec::with_capacity(BATCH)).collect();
+        // Only fails when the worker panicked, which the join below reports
+        let flush = |batches: &mut Vec<Batch>, shard: usize| {
+            let batch = std::mem::replace(&mut batches[shard], Vec::with_capacity(BATCH));
+            let _ = senders[shard].send(Message::Rows(batch));
+        };
+        let mut transferred = vec![];
+        let routed = (|| -> Result<(), EnumError> {
+            for row in rows {
+                let (row, transaction) = row?;
+                let registered = engine.route(&transaction);
+
+                let shard = transaction.client as usize % threads;
+                let client = match (&transaction.r#type, transaction.destination) {
+                    (EnumType::Transfer, Some(client)) => client,
+                    _ => {
+                        batches[shard].push((row, transaction, registered));
+                        if batches[shard].len() == BATCH {
+                            flush(&mut batches, shard);
+                        }
+                        continue;
+                    }
+                };
+
+                let destination = client as usize % threads;
+                flush(&mut batches, shard);
+                flush(&mut batches, destination);
+                match transfer(
+                    &senders[shard],
+                    &senders[destination],
+                    &transaction,
+                    registered,
+                ) {
+                    Ok(()) => engine.credited(client),
+                    Err(rejection) => transferred.push(Rejected {
+                        row,
+                        transaction,
+                        rejection,
+                    }),
+                }
+            }
+            Ok(())
+        })();
+
+        for shard in 0..threads {
+            flush(&mut batches, shard);
+        }
+        drop(senders);
+
+        let mut shards = vec![];
+        let mut rejected = transferred;
+        for worker in workers {
+            let (shard, mut shard_rejected) = worker
+                .join()
+                .unwrap_or_else(|err| panic::resume_unwind(err));
+            shards.push(shard);
+            rejected.append(&mut shard_rejected);
+        }
+        (shards, routed, rejected)
+    });
+
+    engine.merge(shards);
+    routed?;
+
+    rejected.sort_unstable_by_key(|rejected| rejected.row);
+    Ok(rejected)
+}
+
+fn transfer(
+    source: &mpsc::SyncSender<Message>,
+    destination: &mpsc::SyncSender<Message>,
+    transaction: &CSVParsed,
+    registered: Result<(), EnumRejection>,
+) -> Result<(), EnumRejection> {
+    // A worker that panicked drops the reply, the join reports it
+    let (reply, replied) = mpsc::channel();
+    let _ = source.send(Message::Send(
+        transaction.clone(),
+        registered,
+        reply.clone(),
+    ));
+    replied.recv().unwrap_or(Ok(()))?;
+
+    let _ = destination.send(Message::Receive(transaction.clone(), reply));
+    replied.recv().unwrap_or(Ok(())).inspect_err(|_| {
+        let _ = source.send(Message::Refund(transaction.clone()));
+    })
+}
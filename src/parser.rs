@@ -1,38 +1,119 @@
-use crate::calculation::Position;
+use crate::binary;
+use crate::calculation::{EnumStatus, Position};
+use crate::engine::Exposure;
 use crate::EnumError;
 use csv::Trim;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use serde_json::value::RawValue;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
 use std::str::FromStr;
 
+#[cfg(test)]
+#[path = "test/parser_test.rs"]
+mod parser_test;
+
 #[derive(Deserialize)]
 struct CSVStruct {
+    #[serde(skip)]
+    line: u64,
     r#type: String,
     client: String,
     tx: String,
     amount: String,
+    // Only on a transfer, the column can be left out of files without transfers
+    #[serde(default)]
+    destination: String,
+}
+
+#[derive(Deserialize)]
+struct JsonStruct {
+    r#type: String,
+    client: Option<Box<RawValue>>,
+    tx: Option<Box<RawValue>>,
+    #[serde(default)]
+    amount: Option<Box<RawValue>>,
+    #[serde(default)]
+    destination: Option<Box<RawValue>>,
+}
+
+const AMOUNT_SCALE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EnumInputFormat {
+    #[default]
+    Csv,
+    Jsonl,
+    Binary,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EnumPrecision {
+    #[default]
+    Reject,
+    Round,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CSVParsed {
     pub r#type: EnumType,
     pub client: u16,
+    #[serde(rename = "tx")]
     pub transaction_id: u32,
     pub amount: Option<Decimal>,
+    // Client credited by a transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<u16>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EnumType {
     Deposit,
     Withdrawal,
     Dispute,
     Resolve,
     Chargeback,
+    Transfer,
+    Unlock,
+    Freeze,
+    Close,
+    Reopen,
+}
+
+impl EnumType {
+    // Changes of the account status, only accepted from the operator inputs
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            EnumType::Unlock | EnumType::Freeze | EnumType::Close | EnumType::Reopen
+        )
+    }
+}
+
+impl fmt::Display for EnumType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r#type = match self {
+            EnumType::Deposit => "deposit",
+            EnumType::Withdrawal => "withdrawal",
+            EnumType::Dispute => "dispute",
+            EnumType::Resolve => "resolve",
+            EnumType::Chargeback => "chargeback",
+            EnumType::Transfer => "transfer",
+            EnumType::Unlock => "unlock",
+            EnumType::Freeze => "freeze",
+            EnumType::Close => "close",
+            EnumType::Reopen => "reopen",
+        };
+        write!(f, "{}", r#type)
+    }
 }
 
 impl TryFrom<String> for EnumType {
-    type Error = EnumError;
+    type Error = String;
 
     fn try_from(r#type: String) -> Result<Self, Self::Error> {
         match r#type.as_str() {
@@ -41,7 +122,12 @@ impl TryFrom<String> for EnumType {
             "dispute" => Ok(Self::Dispute),
             "resolve" => Ok(Self::Resolve),
             "chargeback" => Ok(Self::Chargeback),
-            _ => Err(EnumError::InvalidType),
+            "transfer" => Ok(Self::Transfer),
+            "unlock" => Ok(Self::Unlock),
+            "freeze" => Ok(Self::Freeze),
+            "close" => Ok(Self::Close),
+            "reopen" => Ok(Self::Reopen),
+            _ => Err(r#type),
         }
     }
 }
@@ -50,85 +136,504 @@ impl TryFrom<CSVStruct> for CSVParsed {
     type Error = EnumError;
 
     fn try_from(csv_struct: CSVStruct) -> Result<Self, EnumError> {
+        let line = csv_struct.line;
+        let cannot_convert = |column: &str, value: &str| EnumError::CannotConvert {
+            line,
+            column: column.to_string(),
+            value: value.to_string(),
+        };
+
         Ok(Self {
-            r#type: csv_struct.r#type.try_into()?,
+            r#type: csv_struct
+                .r#type
+                .try_into()
+                .map_err(|value| EnumError::InvalidType { line, value })?,
             client: csv_struct
                 .client
                 .trim()
                 .parse::<u16>()
-                .map_err(|_| EnumError::CannotConvert("client".to_string()))?,
+                .map_err(|_| cannot_convert("client", &csv_struct.client))?,
             transaction_id: csv_struct
                 .tx
                 .trim()
                 .parse::<u32>()
-                .map_err(|_| EnumError::CannotConvert("transaction_id".to_string()))?,
+                .map_err(|_| cannot_convert("tx", &csv_struct.tx))?,
             amount: if csv_struct.amount.trim().is_empty() {
                 None
             } else {
                 Some(
                     Decimal::from_str(csv_struct.amount.trim())
-                        .map_err(|_| EnumError::CannotConvert("amount".to_string()))?,
+                        .map_err(|_| cannot_convert("amount", &csv_struct.amount))?,
+                )
+            },
+            destination: if csv_struct.destination.trim().is_empty() {
+                None
+            } else {
+                Some(
+                    csv_struct
+                        .destination
+                        .trim()
+                        .parse::<u16>()
+                        .map_err(|_| cannot_convert("destination", &csv_struct.destination))?,
                 )
             },
         })
     }
 }
 
-pub fn read_csv(file_name: String) -> Result<Vec<CSVParsed>, EnumError> {
-    let contents = fs::read_to_string(file_name).map_err(|_| EnumError::FileNotPresent)?;
+pub const STDIN: &str = "-";
+
+pub fn open_csv(file_name: String) -> Result<File, EnumError> {
+    File::open(&file_name).map_err(|_| EnumError::FileNotPresent(file_name))
+}
+
+pub fn open_input(file_name: &str) -> Result<Box<dyn Read>, EnumError> {
+    if file_name == STDIN {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(open_csv(file_name.to_string())?))
+    }
+}
+
+// Arguments with wildcards are expanded here too, for shells and scripts that pass them quoted
+pub fn expand_inputs(file_names: Vec<String>) -> Result<Vec<String>, EnumError> {
+    let mut inputs = vec![];
+
+    for file_name in file_names {
+        if file_name == STDIN || !file_name.contains(['*', '?', '[']) {
+            inputs.push(file_name);
+            continue;
+        }
+
+        let paths = glob::glob(&file_name)
+            .map_err(|_| EnumError::InvalidArgument(file_name.clone()))?
+            .filter_map(Result::ok)
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Err(EnumError::FileNotPresent(file_name));
+        }
+        inputs.extend(paths);
+    }
+
+    Ok(inputs)
+}
+
+// Every input is opened only when the previous ones are consumed and read as one stream
+pub fn read_inputs(
+    file_names: Vec<String>,
+    format: Option<EnumInputFormat>,
+    precision: EnumPrecision,
+) -> impl Iterator<Item = Result<CSVParsed, EnumError>> {
+    file_names.into_iter().flat_map(move |file_name| {
+        let transactions = match open_input(&file_name) {
+            Ok(reader) => read_transactions(
+                reader,
+                format.unwrap_or_else(|| EnumInputFormat::from_extension(&file_name)),
+                precision,
+            ),
+            Err(err) => Box::new(std::iter::once(Err(err))),
+        };
+
+        let input = match file_name.as_str() {
+            STDIN => "stdin".to_string(),
+            _ => file_name,
+        };
+        transactions.map(move |transaction| {
+            transaction.map_err(|err| match err {
+                EnumError::FileNotPresent(_) => err,
+                _ => EnumError::InFile(input.clone(), Box::new(err)),
+            })
+        })
+    })
+}
+
+impl TryFrom<String> for EnumInputFormat {
+    type Error = EnumError;
+
+    fn try_from(format: String) -> Result<Self, Self::Error> {
+        match format.as_str() {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::Jsonl),
+            "binary" => Ok(Self::Binary),
+            _ => Err(EnumError::InvalidArgument(format)),
+        }
+    }
+}
+
+impl EnumInputFormat {
+    pub fn from_extension(file_name: &str) -> EnumInputFormat {
+        match Path::new(file_name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("jsonl") => Self::Jsonl,
+            Some("bin") => Self::Binary,
+            _ => Self::Csv,
+        }
+    }
+}
+
+// JSON numbers and strings are both accepted and go through the same conversion as the csv fields,
+// numbers are kept as written instead of going through f64
+fn json_field(value: Option<Box<RawValue>>) -> String {
+    match value {
+        None => String::new(),
+        Some(value) => {
+            serde_json::from_str::<String>(value.get()).unwrap_or_else(|_| value.get().to_string())
+        }
+    }
+}
+
+impl From<(u64, JsonStruct)> for CSVStruct {
+    fn from((line, json_struct): (u64, JsonStruct)) -> Self {
+        Self {
+            line,
+            r#type: json_struct.r#type,
+            client: json_field(json_struct.client),
+            tx: json_field(json_struct.tx),
+            amount: json_field(json_struct.amount),
+            destination: json_field(json_struct.destination),
+        }
+    }
+}
 
-    let data: &[u8] = &contents.into_bytes();
-    let mut reader = csv::ReaderBuilder::new().trim(Trim::All).from_reader(data);
+// Checks every field that depends on the type, once a row is parsed
+pub fn validate(
+    transaction: CSVParsed,
+    line: u64,
+    precision: EnumPrecision,
+) -> Result<CSVParsed, EnumError> {
+    validate_destination(&transaction, line)?;
+    validate_amount(transaction, line, precision)
+}
 
-    let csv_result: Result<Vec<CSVStruct>, csv::Error> = reader.deserialize().collect(); // Fail at the first error
-    let csv = csv_result.map_err(|err| {
-        println!("{:?}", err);
-        EnumError::InvalidCSV
-    })?;
+fn validate_destination(transaction: &CSVParsed, line: u64) -> Result<(), EnumError> {
+    let invalid = |reason: String| EnumError::InvalidDestination {
+        line,
+        value: transaction
+            .destination
+            .map_or_else(String::new, |destination| destination.to_string()),
+        reason,
+    };
 
-    csv.into_iter()
-        .map(TryInto::try_into)
-        .collect::<Result<Vec<CSVParsed>, EnumError>>()
+    match (&transaction.r#type, transaction.destination) {
+        (EnumType::Transfer, None) => Err(invalid("required on a transfer".to_string())),
+        (EnumType::Transfer, Some(destination)) if destination == transaction.client => {
+            Err(invalid("same as the client".to_string()))
+        }
+        (EnumType::Transfer, Some(_)) | (_, None) => Ok(()),
+        (_, Some(_)) => Err(invalid(format!("not allowed on a {}", transaction.r#type))),
+    }
 }
 
-#[derive(Serialize)]
-pub struct CSVOutput {
-    client: String,
-    available: String,
-    held: String,
-    total: String,
-    locked: String,
+fn validate_amount(
+    transaction: CSVParsed,
+    line: u64,
+    precision: EnumPrecision,
+) -> Result<CSVParsed, EnumError> {
+    let invalid = |reason: String| EnumError::InvalidAmount {
+        line,
+        value: transaction
+            .amount
+            .map_or_else(String::new, |amount| amount.to_string()),
+        reason,
+    };
+
+    let amount = match (&transaction.r#type, transaction.amount) {
+        (EnumType::Deposit | EnumType::Withdrawal | EnumType::Transfer, Some(amount)) => amount,
+        (EnumType::Deposit | EnumType::Withdrawal | EnumType::Transfer, None) => {
+            return Err(invalid(format!("required on a {}", transaction.r#type)))
+        }
+        // A dispute, resolve or chargeback with an amount only acts on that part of the transaction
+        (EnumType::Dispute | EnumType::Resolve | EnumType::Chargeback, Some(amount)) => amount,
+        (_, None) => return Ok(transaction),
+        (_, Some(_)) => return Err(invalid(format!("not allowed on a {}", transaction.r#type))),
+    };
+
+    let amount = match precision {
+        EnumPrecision::Round => amount.round_dp(AMOUNT_SCALE),
+        EnumPrecision::Reject if amount.normalize().scale() > AMOUNT_SCALE => {
+            return Err(invalid(format!(
+                "more than {} decimal places",
+                AMOUNT_SCALE
+            )))
+        }
+        EnumPrecision::Reject => amount,
+    };
+
+    if amount <= Decimal::ZERO {
+        return Err(invalid("must be positive".to_string()));
+    }
+
+    Ok(CSVParsed {
+        amount: Some(amount),
+        ..transaction
+    })
+}
+
+pub fn read_transactions<'a, R: Read + 'a>(
+    reader: R,
+    format: EnumInputFormat,
+    precision: EnumPrecision,
+) -> Box<dyn Iterator<Item = Result<CSVParsed, EnumError>> + 'a> {
+    match format {
+        EnumInputFormat::Csv => Box::new(read_csv_transactions(reader, precision)),
+        EnumInputFormat::Jsonl => Box::new(read_jsonl_transactions(reader, precision)),
+        EnumInputFormat::Binary => Box::new(binary::read_transactions(reader, precision)),
+    }
+}
+
+fn read_jsonl_transactions<R: Read>(
+    reader: R,
+    precision: EnumPrecision,
+) -> impl Iterator<Item = Result<CSVParsed, EnumError>> {
+    BufReader::new(reader)
+        .lines()
+        .zip(1..)
+        .filter(|(row, _)| !row.as_ref().is_ok_and(|row| row.trim().is_empty()))
+        .map(move |(row, line)| {
+            let row = row.map_err(|err| EnumError::CannotReadInput(err.to_string()))?;
+            let json_struct =
+                serde_json::from_str::<JsonStruct>(&row).map_err(|err| EnumError::InvalidJson {
+                    line,
+                    message: err.to_string(),
+                })?;
+
+            CSVStruct::from((line, json_struct))
+                .try_into()
+                .and_then(|transaction| validate(transaction, line, precision))
+        })
+}
+
+fn read_csv_transactions<R: Read>(
+    reader: R,
+    precision: EnumPrecision,
+) -> impl Iterator<Item = Result<CSVParsed, EnumError>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers().cloned().unwrap_or_default();
+
+    reader.into_records().map(move |record| {
+        let record = record.map_err(EnumError::from)?;
+        let line = record.position().map_or(0, |position| position.line());
+
+        let csv_struct = record
+            .deserialize::<CSVStruct>(Some(&headers))
+            .map_err(EnumError::from)?;
+
+        CSVStruct { line, ..csv_struct }
+            .try_into()
+            .and_then(|transaction| validate(transaction, line, precision))
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EnumFormat {
+    #[default]
+    Csv,
+    Json,
+    Jsonl,
 }
 
-impl ToString for CSVOutput {
-    fn to_string(&self) -> String {
-        self.client.to_string()
-            + ","
-            + self.available.as_str()
-            + ","
-            + self.held.as_str()
-            + ","
-            + self.total.as_str()
-            + ","
-            + self.locked.as_str()
+impl TryFrom<String> for EnumFormat {
+    type Error = EnumError;
+
+    fn try_from(format: String) -> Result<Self, Self::Error> {
+        match format.as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(EnumError::InvalidArgument(format)),
+        }
     }
 }
 
+// Amounts are kept as strings so that no format loses precision on them
+#[derive(Serialize, Deserialize)]
+pub struct CSVOutput {
+    pub client: u16,
+    pub available: String,
+    pub held: String,
+    pub total: String,
+    // True for any status other than active, for the readers that only know this column
+    pub locked: bool,
+    // Missing in positions written before the column existed, the locked flag is used instead
+    #[serde(default)]
+    pub status: Option<EnumStatus>,
+}
+
+fn format_amount(amount: Decimal) -> String {
+    format!(
+        "{:.*}",
+        AMOUNT_SCALE as usize,
+        amount.round_dp(AMOUNT_SCALE).normalize()
+    )
+}
+
 impl From<Position> for CSVOutput {
     fn from(position: Position) -> Self {
         Self {
-            client: position.client.to_string(),
-            available: position.available.to_string(),
-            held: position.held.to_string(),
-            total: position.total.to_string(),
-            locked: position.locked.to_string(),
+            client: position.client,
+            available: format_amount(position.available),
+            held: format_amount(position.held),
+            total: format_amount(position.total),
+            locked: !position.is_active(),
+            status: Some(position.status),
         }
     }
 }
 
-pub fn write_out_positions(positions: Vec<CSVOutput>) {
-    println!("client,available,held,total,locked");
+fn parse_position(line: u64, output: CSVOutput) -> Result<Position, EnumError> {
+    let amount = |column: &str, value: &str| {
+        Decimal::from_str(value).map_err(|_| EnumError::CannotConvert {
+            line,
+            column: column.to_string(),
+            value: value.to_string(),
+        })
+    };
+
+    let position = Position {
+        client: output.client,
+        available: amount("available", &output.available)?,
+        held: amount("held", &output.held)?,
+        total: amount("total", &output.total)?,
+        status: match (output.status, output.locked) {
+            (Some(status), _) => status,
+            (None, true) => EnumStatus::Locked,
+            (None, false) => EnumStatus::Active,
+        },
+    };
+
+    if position.available + position.held != position.total {
+        return Err(EnumError::InvalidAmount {
+            line,
+            value: output.total,
+            reason: "total is not available plus held".to_string(),
+        });
+    }
+    if position.held < Decimal::ZERO {
+        return Err(EnumError::InvalidAmount {
+            line,
+            value: output.held,
+            reason: "held cannot be negative".to_string(),
+        });
+    }
+
+    Ok(position)
+}
+
+// Reads positions in the csv format written by `write_out_positions`
+pub fn read_positions<R: Read>(reader: R) -> impl Iterator<Item = Result<Position, EnumError>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers().cloned().unwrap_or_default();
+
+    reader.into_records().map(move |record| {
+        let record = record.map_err(EnumError::from)?;
+        let line = record.position().map_or(0, |position| position.line());
+
+        let output = record
+            .deserialize::<CSVOutput>(Some(&headers))
+            .map_err(EnumError::from)?;
+
+        parse_position(line, output)
+    })
+}
+
+pub fn write_exposures<W, I>(writer: W, exposures: I) -> Result<(), EnumError>
+where
+    W: Write,
+    I: IntoIterator<Item = Exposure>,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    writer
+        .write_record(["client", "available", "shortfall"])
+        .map_err(|_| EnumError::CannotWriteLine)?;
+
+    for exposure in exposures {
+        writer
+            .write_record([
+                exposure.client.to_string(),
+                format_amount(exposure.available),
+                format_amount(exposure.shortfall),
+            ])
+            .map_err(|_| EnumError::CannotWriteLine)?;
+    }
+
+    writer.flush().map_err(|_| EnumError::CannotWriteLine)
+}
+
+pub fn write_out_positions<W, I>(
+    writer: W,
+    format: EnumFormat,
+    positions: I,
+) -> Result<(), EnumError>
+where
+    W: Write,
+    I: IntoIterator<Item = CSVOutput>,
+{
+    match format {
+        EnumFormat::Csv => write_csv(writer, positions),
+        EnumFormat::Json => write_json(writer, positions),
+        EnumFormat::Jsonl => write_jsonl(writer, positions),
+    }
+}
+
+fn write_csv<W, I>(writer: W, positions: I) -> Result<(), EnumError>
+where
+    W: Write,
+    I: IntoIterator<Item = CSVOutput>,
+{
+    // The header is written explicitly so that it is there even without any position
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    writer
+        .write_record(["client", "available", "held", "total", "locked", "status"])
+        .map_err(|_| EnumError::CannotWriteLine)?;
+
+    for position in positions {
+        writer
+            .serialize(position)
+            .map_err(|_| EnumError::CannotWriteLine)?;
+    }
+
+    writer.flush().map_err(|_| EnumError::CannotWriteLine)
+}
+
+// The array is written one position at a time instead of collecting it first
+fn write_json<W, I>(mut writer: W, positions: I) -> Result<(), EnumError>
+where
+    W: Write,
+    I: IntoIterator<Item = CSVOutput>,
+{
+    let mut separator = "\n";
+
+    write!(writer, "[").map_err(|_| EnumError::CannotWriteLine)?;
+    for position in positions {
+        write!(writer, "{}", separator).map_err(|_| EnumError::CannotWriteLine)?;
+        serde_json::to_writer(&mut writer, &position).map_err(|_| EnumError::CannotWriteLine)?;
+        separator = ",\n";
+    }
+    writeln!(writer, "\n]").map_err(|_| EnumError::CannotWriteLine)?;
+
+    writer.flush().map_err(|_| EnumError::CannotWriteLine)
+}
+
+fn write_jsonl<W, I>(mut writer: W, positions: I) -> Result<(), EnumError>
+where
+    W: Write,
+    I: IntoIterator<Item = CSVOutput>,
+{
     for position in positions {
-        println!("{}", position.to_string())
+        serde_json::to_writer(&mut writer, &position).map_err(|_| EnumError::CannotWriteLine)?;
+        writeln!(writer).map_err(|_| EnumError::CannotWriteLine)?;
     }
+
+    writer.flush().map_err(|_| EnumError::CannotWriteLine)
 }
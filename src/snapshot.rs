@@ -0,0 +1,70 @@
+use crate::calculation::Position;
+use crate::store::TransactionStore;
+use crate::EnumError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+
+#[cfg(test)]
+#[path = "test/snapshot_test.rs"]
+mod snapshot_test;
+
+// Bumped whenever the layout changes, an older snapshot is refused rather than misread
+pub const VERSION: u32 = 5;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    pub positions: Vec<Position>,
+    pub store: TransactionStore,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl Snapshot {
+    pub fn new(positions: Vec<Position>, store: TransactionStore) -> Snapshot {
+        Snapshot {
+            version: VERSION,
+            positions,
+            store,
+        }
+    }
+}
+
+// Anything written with the snapshot layout carries a top level `version`
+pub fn load<T: DeserializeOwned, R: Read>(reader: R) -> Result<T, EnumError> {
+    let mut contents = String::new();
+    BufReader::new(reader)
+        .read_to_string(&mut contents)
+        .map_err(|err| EnumError::CannotReadInput(err.to_string()))?;
+
+    let header: Header = serde_json::from_str(&contents)
+        .map_err(|err| EnumError::InvalidSnapshot(err.to_string()))?;
+    if header.version != VERSION {
+        return Err(EnumError::InvalidSnapshot(format!(
+            "version {} is not supported, expected {}",
+            header.version, VERSION
+        )));
+    }
+
+    serde_json::from_str(&contents).map_err(|err| EnumError::InvalidSnapshot(err.to_string()))
+}
+
+pub fn write<W: Write, T: Serialize>(mut writer: W, snapshot: &T) -> Result<(), EnumError> {
+    serde_json::to_writer(&mut writer, snapshot).map_err(|_| EnumError::CannotWriteLine)?;
+    writer.flush().map_err(|_| EnumError::CannotWriteLine)
+}
+
+// Written next to the target and renamed, so a failed run never leaves half a snapshot behind
+pub fn save<T: Serialize>(file_name: &str, snapshot: &T) -> Result<(), EnumError> {
+    let temporary = format!("{}.tmp", file_name);
+    write(
+        BufWriter::new(File::create(&temporary).map_err(|_| EnumError::CannotWriteCsv)?),
+        snapshot,
+    )?;
+    fs::rename(&temporary, file_name).map_err(|_| EnumError::CannotWriteCsv)
+}
@@ -0,0 +1,169 @@
+use crate::calculation::EnumRejection;
+use crate::{CSVParsed, EnumType};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+#[cfg(test)]
+#[path = "test/store_test.rs"]
+mod store_test;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredTransaction {
+    pub client: u16,
+    pub r#type: EnumType,
+    pub amount: Decimal,
+    // Part of the amount under an open dispute, and the part of it held, less when the funds were short
+    pub disputed: Decimal,
+    pub held: Decimal,
+    pub state: EnumState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnumState {
+    Processed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct TransactionStore {
+    transactions: HashMap<u32, StoredTransaction>,
+    // Client of every deposit and withdrawal id, applied or not
+    seen: HashMap<u32, u16>,
+}
+
+impl StoredTransaction {
+    // Settles the amount of a resolve or chargeback, or the whole dispute without one, the held funds
+    // first. The dispute is closed with `state` once nothing is left disputed. Returns the amount
+    // settled and the part of it that was held.
+    pub fn settle(
+        &mut self,
+        transaction: &CSVParsed,
+        state: EnumState,
+    ) -> Result<(Decimal, Decimal), EnumRejection> {
+        if self.state != EnumState::Disputed {
+            return Err(EnumRejection::DisputeNotOpen);
+        }
+        let settled = transaction.amount.unwrap_or(self.disputed);
+        if settled > self.disputed {
+            return Err(EnumRejection::ExceedsDispute);
+        }
+
+        let held = settled.min(self.held);
+        self.disputed -= settled;
+        self.held -= held;
+        if self.disputed.is_zero() {
+            self.state = state;
+        }
+        Ok((settled, held))
+    }
+
+    // Takes a settled part out of what can be disputed, the rest of the transaction can be disputed again
+    pub fn close(&mut self, settled: Decimal) {
+        self.amount -= settled;
+        if self.state != EnumState::Disputed && !self.amount.is_zero() {
+            self.state = EnumState::Processed;
+        }
+    }
+}
+
+impl TransactionStore {
+    // Deposit and withdrawal ids are unique across every client, even when the row is not applied,
+    // and the other types can only refer to an id of the same client
+    pub fn register(&mut self, transaction: &CSVParsed) -> Result<(), EnumRejection> {
+        match transaction.r#type {
+            EnumType::Deposit | EnumType::Withdrawal | EnumType::Transfer => {
+                match self.seen.entry(transaction.transaction_id) {
+                    Entry::Occupied(_) => Err(EnumRejection::DuplicateTransaction),
+                    Entry::Vacant(entry) => {
+                        entry.insert(transaction.client);
+                        Ok(())
+                    }
+                }
+            }
+            _ => match self.seen.get(&transaction.transaction_id) {
+                Some(client) if *client != transaction.client => Err(EnumRejection::WrongClient),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    // Amount of the open disputes of each client that could not be held
+    pub fn shortfalls(&self) -> HashMap<u16, Decimal> {
+        let mut shortfalls = HashMap::new();
+        for stored in self.transactions.values() {
+            if stored.state == EnumState::Disputed && stored.held < stored.disputed {
+                *shortfalls.entry(stored.client).or_insert(Decimal::ZERO) +=
+                    stored.disputed - stored.held;
+            }
+        }
+        shortfalls
+    }
+
+    // Shards the transactions by `client % shards`, the ids seen stay with `self`
+    pub(crate) fn split(&mut self, shards: usize) -> Vec<TransactionStore> {
+        let mut split: Vec<TransactionStore> = (0..shards).map(|_| Default::default()).collect();
+        for (transaction_id, stored) in self.transactions.drain() {
+            split[stored.client as usize % shards]
+                .transactions
+                .insert(transaction_id, stored);
+        }
+        split
+    }
+
+    pub(crate) fn merge(&mut self, shard: TransactionStore) {
+        self.transactions.extend(shard.transactions);
+        self.seen.extend(shard.seen);
+    }
+
+    pub fn insert(&mut self, transaction: &CSVParsed) {
+        if let Some(amount) = transaction.amount {
+            self.transactions.insert(
+                transaction.transaction_id,
+                StoredTransaction {
+                    client: transaction.client,
+                    r#type: transaction.r#type.clone(),
+                    amount,
+                    disputed: Decimal::ZERO,
+                    held: Decimal::ZERO,
+                    state: EnumState::Processed,
+                },
+            );
+        }
+    }
+
+    // Client that used an id and the transaction stored for it, for the journal checkpoints
+    pub(crate) fn get(&self, transaction_id: u32) -> Option<(u16, Option<&StoredTransaction>)> {
+        self.seen
+            .get(&transaction_id)
+            .map(|client| (*client, self.transactions.get(&transaction_id)))
+    }
+
+    pub(crate) fn put(
+        &mut self,
+        transaction_id: u32,
+        client: u16,
+        stored: Option<StoredTransaction>,
+    ) {
+        self.seen.insert(transaction_id, client);
+        if let Some(stored) = stored {
+            self.transactions.insert(transaction_id, stored);
+        }
+    }
+
+    // A dispute, resolve or chargeback can only refer to a transaction of the same client
+    pub fn get_mut(
+        &mut self,
+        transaction: &CSVParsed,
+    ) -> Result<&mut StoredTransaction, EnumRejection> {
+        match self.transactions.get_mut(&transaction.transaction_id) {
+            Some(stored) if stored.client == transaction.client => Ok(stored),
+            Some(_) => Err(EnumRejection::WrongClient),
+            None => Err(EnumRejection::UnknownTransaction),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::async_engine::AsyncEngine;
+use crate::calculation::{EnumRejection, EnumStatus, Position};
+use crate::engine::{Engine, EnumOrder};
+use crate::parser::{read_transactions, CSVParsed, EnumInputFormat, EnumPrecision, EnumType};
+use futures_util::stream::{self, StreamExt};
+use rust_decimal::Decimal;
+
+const INPUT: &str = "type,client,tx,amount
+deposit,2,1,2.0
+deposit,1,2,1.0
+withdrawal,1,3,1.5
+dispute,2,1,
+deposit,1,2,1.0
+";
+
+#[tokio::test]
+async fn stream_is_applied_like_the_engine_does() {
+    let transactions: Vec<_> = read_transactions(
+        INPUT.as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+
+    let mut serial = Engine::default();
+    let expected: Vec<_> = transactions
+        .iter()
+        .map(|transaction| serial.process(transaction))
+        .collect();
+
+    let engine = AsyncEngine::default();
+    let results: Vec<_> = engine
+        .process_stream(stream::iter(transactions))
+        .map(|(_, result)| result)
+        .collect()
+        .await;
+
+    assert_eq!(expected, results);
+    assert_eq!(Err(EnumRejection::InsufficientFunds), results[2]);
+    assert_eq!(
+        serial.into_positions(EnumOrder::Client),
+        engine.positions(EnumOrder::Client).await
+    );
+    assert_eq!(
+        Some(Position {
+            client: 2,
+            available: Decimal::new(0, 1),
+            held: Decimal::new(20, 1),
+            total: Decimal::new(20, 1),
+            status: EnumStatus::Active,
+        }),
+        engine.position(2).await
+    );
+}
+
+#[tokio::test]
+async fn tasks_share_the_engine() {
+    let engine = AsyncEngine::default();
+
+    let tasks: Vec<_> = (1..=4u32)
+        .map(|transaction_id| {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                engine
+                    .process(&CSVParsed {
+                        r#type: EnumType::Deposit,
+                        client: 1,
+                        transaction_id,
+                        amount: Some(Decimal::new(1, 0)),
+                        destination: None,
+                    })
+                    .await
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+
+    assert_eq!(Decimal::new(4, 0), engine.position(1).await.unwrap().total);
+}
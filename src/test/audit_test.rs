@@ -0,0 +1,73 @@
+use crate::audit::{AuditLog, EnumAuditFormat};
+use crate::calculation::EnumRejection;
+use crate::{CSVParsed, EnumType};
+use rust_decimal::Decimal;
+
+fn write(format: EnumAuditFormat) -> String {
+    let mut buffer = vec![];
+    let mut audit = AuditLog::new(&mut buffer, format);
+
+    audit
+        .record(
+            &CSVParsed {
+                r#type: EnumType::Deposit,
+                client: 1,
+                transaction_id: 1,
+                amount: Some(Decimal::new(15, 1)),
+                destination: None,
+            },
+            &Ok(()),
+        )
+        .unwrap();
+    audit
+        .record(
+            &CSVParsed {
+                r#type: EnumType::Withdrawal,
+                client: 1,
+                transaction_id: 2,
+                amount: Some(Decimal::new(3, 0)),
+                destination: None,
+            },
+            &Err(EnumRejection::InsufficientFunds),
+        )
+        .unwrap();
+    audit
+        .record(
+            &CSVParsed {
+                r#type: EnumType::Resolve,
+                client: 1,
+                transaction_id: 1,
+                amount: None,
+                destination: None,
+            },
+            &Err(EnumRejection::DisputeNotOpen),
+        )
+        .unwrap();
+    audit.flush().unwrap();
+    drop(audit);
+
+    String::from_utf8(buffer).unwrap()
+}
+
+#[test]
+fn audit_as_csv() {
+    assert_eq!(
+        "type,client,tx,amount,destination,outcome,reason
+deposit,1,1,1.5,,applied,
+withdrawal,1,2,3,,rejected,insufficient_funds
+resolve,1,1,,,rejected,dispute_not_open
+",
+        write(EnumAuditFormat::Csv)
+    );
+}
+
+#[test]
+fn audit_as_json_lines() {
+    assert_eq!(
+        r#"{"type":"deposit","client":1,"tx":1,"amount":"1.5","destination":null,"outcome":"applied","reason":null}
+{"type":"withdrawal","client":1,"tx":2,"amount":"3","destination":null,"outcome":"rejected","reason":"insufficient_funds"}
+{"type":"resolve","client":1,"tx":1,"amount":null,"destination":null,"outcome":"rejected","reason":"dispute_not_open"}
+"#,
+        write(EnumAuditFormat::Jsonl)
+    );
+}
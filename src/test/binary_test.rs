@@ -0,0 +1,109 @@
+use crate::binary::read_transactions;
+use crate::parser::EnumPrecision;
+use crate::{EnumError, EnumType};
+use rust_decimal::Decimal;
+
+fn record(r#type: u8, client: u16, tx: u32, amount: Option<(i64, u8)>) -> Vec<u8> {
+    let mut payload = vec![r#type];
+    payload.extend(client.to_le_bytes());
+    payload.extend(tx.to_le_bytes());
+    if let Some((mantissa, scale)) = amount {
+        payload.extend(mantissa.to_le_bytes());
+        payload.push(scale);
+    }
+
+    let mut record = (payload.len() as u16).to_le_bytes().to_vec();
+    record.extend(payload);
+    record
+}
+
+#[test]
+fn records_are_read() {
+    let input = [
+        record(0, 1, 1, Some((15, 1))),
+        record(2, 1, 1, None),
+        record(1, 2, 70000, Some((12345, 4))),
+    ]
+    .concat();
+
+    let transactions = read_transactions(input.as_slice(), EnumPrecision::Reject)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(3, transactions.len());
+    assert_eq!(EnumType::Deposit, transactions[0].r#type);
+    assert_eq!(Some(Decimal::new(15, 1)), transactions[0].amount);
+    assert_eq!(EnumType::Dispute, transactions[1].r#type);
+    assert_eq!(None, transactions[1].amount);
+    assert_eq!(2, transactions[2].client);
+    assert_eq!(70000, transactions[2].transaction_id);
+    assert_eq!(Some(Decimal::new(12345, 4)), transactions[2].amount);
+}
+
+#[test]
+fn transfer_carries_its_destination() {
+    let mut input = record(9, 1, 1, Some((15, 1)));
+    input.extend(2u16.to_le_bytes());
+    input[0] += 2;
+
+    let transaction = read_transactions(input.as_slice(), EnumPrecision::Reject)
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(EnumType::Transfer, transaction.r#type);
+    assert_eq!(Some(Decimal::new(15, 1)), transaction.amount);
+    assert_eq!(Some(2), transaction.destination);
+}
+
+#[test]
+fn invalid_record_can_be_skipped() {
+    let input = [
+        record(10, 1, 1, Some((15, 1))),
+        record(0, 1, 2, Some((-15, 1))),
+        record(0, 1, 3, Some((15, 1))),
+    ]
+    .concat();
+
+    let results: Vec<_> = read_transactions(input.as_slice(), EnumPrecision::Reject).collect();
+
+    assert_eq!(3, results.len());
+    assert_eq!(
+        "line 1: unknown transaction type '10'",
+        results[0].as_ref().unwrap_err().to_string()
+    );
+    assert!(results[1].as_ref().is_err_and(EnumError::is_malformed_row));
+    assert_eq!(3, results[2].as_ref().unwrap().transaction_id);
+}
+
+#[test]
+fn truncated_record_stops_the_stream() {
+    let mut input = [
+        record(0, 1, 1, Some((15, 1))),
+        record(0, 1, 2, Some((15, 1))),
+    ]
+    .concat();
+    input.truncate(input.len() - 3);
+
+    let results: Vec<_> = read_transactions(input.as_slice(), EnumPrecision::Reject).collect();
+
+    assert_eq!(2, results.len());
+    assert!(results[0].is_ok());
+    assert_eq!(
+        "record 2: invalid binary record: truncated record",
+        results[1].as_ref().unwrap_err().to_string()
+    );
+    assert!(!results[1].as_ref().is_err_and(EnumError::is_malformed_row));
+}
+
+#[test]
+fn unexpected_payload_length() {
+    let input = [3, 0, 0, 1, 0];
+
+    let results: Vec<_> = read_transactions(&input[..], EnumPrecision::Reject).collect();
+
+    assert_eq!(
+        "record 1: invalid binary record: payload of 3 bytes, expected 7, 16 or 18",
+        results[0].as_ref().unwrap_err().to_string()
+    );
+}
@@ -1,107 +1,11 @@
-use crate::calculation::{get_transaction_amount, has_a_dispute_not_closed, Position};
+use crate::calculation::{
+    EnumNegativeBalance, EnumRejection, EnumStatus, EnumWithdrawalDispute, Policy, Position,
+};
+use crate::store::TransactionStore;
 use crate::{CSVParsed, EnumType};
 use rust_decimal::prelude::Zero;
 use rust_decimal::Decimal;
 
-#[test]
-fn has_a_dispute_on_a_transaction() {
-    let transaction = &CSVParsed {
-        r#type: EnumType::Chargeback,
-        client: 1,
-        transaction_id: 2,
-        amount: None,
-    };
-
-    let transactions = &vec![
-        CSVParsed {
-            r#type: EnumType::Deposit,
-            client: 1,
-            transaction_id: 1,
-            amount: Some(Decimal::new(10, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Withdrawal,
-            client: 1,
-            transaction_id: 2,
-            amount: Some(Decimal::new(3, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Withdrawal,
-            client: 1,
-            transaction_id: 3,
-            amount: Some(Decimal::new(3, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Dispute,
-            client: 1,
-            transaction_id: 2,
-            amount: None,
-        },
-    ];
-
-    assert_eq!(true, has_a_dispute_not_closed(transaction, transactions),);
-
-    let transaction = &CSVParsed {
-        r#type: EnumType::Chargeback,
-        client: 1,
-        transaction_id: 1,
-        amount: None,
-    };
-
-    assert_eq!(false, has_a_dispute_not_closed(transaction, transactions),);
-}
-
-#[test]
-fn transaction_amount() {
-    let transaction = &CSVParsed {
-        r#type: EnumType::Chargeback,
-        client: 1,
-        transaction_id: 2,
-        amount: None,
-    };
-
-    let transactions = &vec![
-        CSVParsed {
-            r#type: EnumType::Deposit,
-            client: 1,
-            transaction_id: 1,
-            amount: Some(Decimal::new(10, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Withdrawal,
-            client: 1,
-            transaction_id: 2,
-            amount: Some(Decimal::new(3, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Withdrawal,
-            client: 1,
-            transaction_id: 3,
-            amount: Some(Decimal::new(3, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Dispute,
-            client: 1,
-            transaction_id: 2,
-            amount: None,
-        },
-    ];
-
-    assert_eq!(
-        Some(Decimal::new(3, 2)),
-        get_transaction_amount(transaction, transactions)
-    );
-
-    let transaction = &CSVParsed {
-        r#type: EnumType::Chargeback,
-        client: 1,
-        transaction_id: 5,
-        amount: None,
-    };
-
-    assert_eq!(None, get_transaction_amount(transaction, transactions));
-}
-
 #[test]
 fn no_position_changed_with_only_chargeback() {
     let pos = Position::new(1);
@@ -110,26 +14,15 @@ fn no_position_changed_with_only_chargeback() {
         client: 1,
         transaction_id: 5,
         amount: None,
+        destination: None,
     };
 
-    let transactions = &vec![CSVParsed {
-        r#type: EnumType::Chargeback,
-        client: 1,
-        transaction_id: 5,
-        amount: None,
-    }];
-
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let store = &mut TransactionStore::default();
 
-    let result = Position {
-        client: 1,
-        available: Decimal::zero(),
-        held: Decimal::zero(),
-        total: Decimal::zero(),
-        locked: false,
-    };
-
-    assert_eq!(result, pos);
+    assert_eq!(
+        Err(EnumRejection::UnknownTransaction),
+        pos.manage_transaction(&transaction, store, &Policy::default())
+    );
 }
 
 #[test]
@@ -140,26 +33,15 @@ fn no_position_changed_with_only_withdrawal() {
         client: 1,
         transaction_id: 5,
         amount: Some(Decimal::new(10, 2)),
+        destination: None,
     };
 
-    let transactions = &vec![CSVParsed {
-        r#type: EnumType::Withdrawal,
-        client: 1,
-        transaction_id: 5,
-        amount: Some(Decimal::new(10, 2)),
-    }];
+    let store = &mut TransactionStore::default();
 
-    let pos = pos.manage_transaction(&transaction, transactions);
-
-    let result = Position {
-        client: 1,
-        available: Decimal::zero(),
-        held: Decimal::zero(),
-        total: Decimal::zero(),
-        locked: false,
-    };
-
-    assert_eq!(result, pos);
+    assert_eq!(
+        Err(EnumRejection::InsufficientFunds),
+        pos.manage_transaction(&transaction, store, &Policy::default())
+    );
 }
 
 #[test]
@@ -170,23 +52,21 @@ fn position_changed_with_deposit() {
         client: 1,
         transaction_id: 5,
         amount: Some(Decimal::new(10, 2)),
+        destination: None,
     };
 
-    let transactions = &vec![CSVParsed {
-        r#type: EnumType::Deposit,
-        client: 1,
-        transaction_id: 5,
-        amount: Some(Decimal::new(10, 2)),
-    }];
+    let store = &mut TransactionStore::default();
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let result = Position {
         client: 1,
         available: Decimal::new(10, 2),
         held: Decimal::zero(),
         total: Decimal::new(10, 2),
-        locked: false,
+        status: EnumStatus::Active,
     };
 
     assert_eq!(result, pos);
@@ -196,45 +76,38 @@ fn position_changed_with_deposit() {
 fn position_changed_with_deposit_and_a_partial_withdrawal() {
     let pos = Position::new(1);
 
-    let transactions = &vec![
-        CSVParsed {
-            r#type: EnumType::Deposit,
-            client: 1,
-            transaction_id: 1,
-            amount: Some(Decimal::new(10, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Withdrawal,
-            client: 1,
-            transaction_id: 2,
-            amount: Some(Decimal::new(5, 2)),
-        },
-    ];
+    let store = &mut TransactionStore::default();
 
     let transaction = CSVParsed {
         r#type: EnumType::Deposit,
         client: 1,
         transaction_id: 1,
         amount: Some(Decimal::new(10, 2)),
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let transaction = CSVParsed {
         r#type: EnumType::Withdrawal,
         client: 1,
         transaction_id: 2,
         amount: Some(Decimal::new(5, 2)),
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let result = Position {
         client: 1,
         available: Decimal::new(5, 2),
         held: Decimal::zero(),
         total: Decimal::new(5, 2),
-        locked: false,
+        status: EnumStatus::Active,
     };
 
     assert_eq!(result, pos);
@@ -244,108 +117,82 @@ fn position_changed_with_deposit_and_a_partial_withdrawal() {
 fn position_not_changed_with_deposit_and_a_withdrawal_more_than_available() {
     let pos = Position::new(1);
 
-    let transactions = &vec![
-        CSVParsed {
-            r#type: EnumType::Deposit,
-            client: 1,
-            transaction_id: 1,
-            amount: Some(Decimal::new(10, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Withdrawal,
-            client: 1,
-            transaction_id: 2,
-            amount: Some(Decimal::new(15, 2)),
-        },
-    ];
+    let store = &mut TransactionStore::default();
 
     let transaction = CSVParsed {
         r#type: EnumType::Deposit,
         client: 1,
         transaction_id: 1,
         amount: Some(Decimal::new(10, 2)),
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let transaction = CSVParsed {
         r#type: EnumType::Withdrawal,
         client: 1,
         transaction_id: 2,
         amount: Some(Decimal::new(15, 2)),
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
-
-    let result = Position {
-        client: 1,
-        available: Decimal::new(10, 2),
-        held: Decimal::zero(),
-        total: Decimal::new(10, 2),
-        locked: false,
-    };
-
-    assert_eq!(result, pos);
+    assert_eq!(
+        Err(EnumRejection::InsufficientFunds),
+        pos.manage_transaction(&transaction, store, &Policy::default())
+    );
 }
 
 #[test]
 fn position_locked_after_a_chargeback_on_dispute() {
     let pos = Position::new(1);
 
-    let transactions = &vec![
-        CSVParsed {
-            r#type: EnumType::Deposit,
-            client: 1,
-            transaction_id: 1,
-            amount: Some(Decimal::new(10, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Dispute,
-            client: 1,
-            transaction_id: 1,
-            amount: None,
-        },
-        CSVParsed {
-            r#type: EnumType::Chargeback,
-            client: 1,
-            transaction_id: 1,
-            amount: None,
-        },
-    ];
+    let store = &mut TransactionStore::default();
 
     let transaction = CSVParsed {
         r#type: EnumType::Deposit,
         client: 1,
         transaction_id: 1,
         amount: Some(Decimal::new(10, 2)),
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let transaction = CSVParsed {
         r#type: EnumType::Dispute,
         client: 1,
         transaction_id: 1,
         amount: None,
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let transaction = CSVParsed {
         r#type: EnumType::Chargeback,
         client: 1,
         transaction_id: 1,
         amount: None,
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let result = Position {
         client: 1,
         available: Decimal::new(0, 2),
         held: Decimal::zero(),
         total: Decimal::new(0, 2),
-        locked: true,
+        status: EnumStatus::Locked,
     };
 
     assert_eq!(result, pos);
@@ -355,48 +202,26 @@ fn position_locked_after_a_chargeback_on_dispute() {
 fn position_not_locked_after_a_chargeback_on_resolved_dispute() {
     let pos = Position::new(1);
 
-    let transactions = &vec![
-        CSVParsed {
-            r#type: EnumType::Deposit,
-            client: 1,
-            transaction_id: 1,
-            amount: Some(Decimal::new(10, 2)),
-        },
-        CSVParsed {
-            r#type: EnumType::Dispute,
-            client: 1,
-            transaction_id: 1,
-            amount: None,
-        },
-        CSVParsed {
-            r#type: EnumType::Resolve,
-            client: 1,
-            transaction_id: 1,
-            amount: None,
-        },
-        CSVParsed {
-            r#type: EnumType::Chargeback,
-            client: 1,
-            transaction_id: 1,
-            amount: None,
-        },
-    ];
+    let store = &mut TransactionStore::default();
 
     let transaction = CSVParsed {
         r#type: EnumType::Deposit,
         client: 1,
         transaction_id: 1,
         amount: Some(Decimal::new(10, 2)),
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let result = Position {
         client: 1,
         available: Decimal::new(10, 2),
         held: Decimal::zero(),
         total: Decimal::new(10, 2),
-        locked: false,
+        status: EnumStatus::Active,
     };
 
     assert_eq!(result, pos);
@@ -406,16 +231,19 @@ fn position_not_locked_after_a_chargeback_on_resolved_dispute() {
         client: 1,
         transaction_id: 1,
         amount: None,
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let result = Position {
         client: 1,
         available: Decimal::zero(),
         held: Decimal::new(10, 2),
         total: Decimal::new(10, 2),
-        locked: false,
+        status: EnumStatus::Active,
     };
 
     assert_eq!(result, pos);
@@ -425,16 +253,19 @@ fn position_not_locked_after_a_chargeback_on_resolved_dispute() {
         client: 1,
         transaction_id: 1,
         amount: None,
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    let pos = pos
+        .manage_transaction(&transaction, store, &Policy::default())
+        .unwrap();
 
     let result = Position {
         client: 1,
         available: Decimal::new(10, 2),
         held: Decimal::zero(),
         total: Decimal::new(10, 2),
-        locked: false,
+        status: EnumStatus::Active,
     };
 
     assert_eq!(result, pos);
@@ -444,9 +275,505 @@ fn position_not_locked_after_a_chargeback_on_resolved_dispute() {
         client: 1,
         transaction_id: 1,
         amount: None,
+        destination: None,
     };
 
-    let pos = pos.manage_transaction(&transaction, transactions);
+    assert_eq!(
+        Err(EnumRejection::DisputeNotOpen),
+        pos.manage_transaction(&transaction, store, &Policy::default())
+    );
+}
 
-    assert_eq!(result, pos);
+fn dispute_lifecycle(policy: &Policy) -> Vec<Result<Position, EnumRejection>> {
+    let store = &mut TransactionStore::default();
+    let mut pos = Position::new(1);
+
+    [
+        (EnumType::Deposit, Some(Decimal::new(10, 2))),
+        (EnumType::Dispute, None),
+        (EnumType::Dispute, None),
+        (EnumType::Resolve, None),
+        (EnumType::Resolve, None),
+        (EnumType::Dispute, None),
+    ]
+    .into_iter()
+    .map(|(r#type, amount)| {
+        let transaction = CSVParsed {
+            r#type,
+            client: 1,
+            transaction_id: 1,
+            amount,
+            destination: None,
+        };
+        let result = pos.manage_transaction(&transaction, store, policy);
+        if let Ok(updated) = result {
+            pos = updated;
+        }
+        result
+    })
+    .collect()
+}
+
+#[test]
+fn duplicate_dispute_and_resolve_are_rejected() {
+    let results = dispute_lifecycle(&Policy::default());
+
+    assert_eq!(Err(EnumRejection::AlreadyDisputed), results[2]);
+    assert_eq!(Err(EnumRejection::DisputeNotOpen), results[4]);
+    assert_eq!(Err(EnumRejection::DisputeClosed), results[5]);
+}
+
+#[test]
+fn redispute_after_resolve_when_allowed() {
+    let results = dispute_lifecycle(&Policy {
+        allow_redispute: true,
+        ..Default::default()
+    });
+
+    assert_eq!(
+        Ok(Position {
+            client: 1,
+            available: Decimal::zero(),
+            held: Decimal::new(10, 2),
+            total: Decimal::new(10, 2),
+            status: EnumStatus::Active,
+        }),
+        results[5]
+    );
+}
+
+#[test]
+fn locked_account_rejects_every_transaction() {
+    let pos = Position {
+        status: EnumStatus::Locked,
+        ..Position::new(1)
+    };
+    let transaction = CSVParsed {
+        r#type: EnumType::Deposit,
+        client: 1,
+        transaction_id: 1,
+        amount: Some(Decimal::new(10, 2)),
+        destination: None,
+    };
+
+    assert_eq!(
+        Err(EnumRejection::AccountLocked),
+        pos.manage_transaction(
+            &transaction,
+            &mut TransactionStore::default(),
+            &Policy::default()
+        )
+    );
+}
+
+#[test]
+fn rejected_withdrawal_cannot_be_disputed() {
+    let store = &mut TransactionStore::default();
+    let pos = Position::new(1);
+
+    let transaction = CSVParsed {
+        r#type: EnumType::Withdrawal,
+        client: 1,
+        transaction_id: 1,
+        amount: Some(Decimal::new(10, 2)),
+        destination: None,
+    };
+    assert_eq!(
+        Err(EnumRejection::InsufficientFunds),
+        pos.manage_transaction(&transaction, store, &Policy::default())
+    );
+
+    let transaction = CSVParsed {
+        r#type: EnumType::Dispute,
+        client: 1,
+        transaction_id: 1,
+        amount: None,
+        destination: None,
+    };
+    assert_eq!(
+        Err(EnumRejection::UnknownTransaction),
+        pos.manage_transaction(&transaction, store, &Policy::default())
+    );
+}
+
+fn disputed_withdrawal(
+    withdrawal_dispute: EnumWithdrawalDispute,
+    closing: EnumType,
+) -> Vec<Result<Position, EnumRejection>> {
+    let store = &mut TransactionStore::default();
+    let policy = Policy {
+        withdrawal_dispute,
+        ..Default::default()
+    };
+    let mut pos = Position::new(1);
+
+    [
+        (EnumType::Deposit, 1, Some(Decimal::new(10, 0))),
+        (EnumType::Withdrawal, 2, Some(Decimal::new(4, 0))),
+        (EnumType::Dispute, 2, None),
+        (closing, 2, None),
+    ]
+    .into_iter()
+    .map(|(r#type, transaction_id, amount)| {
+        let transaction = CSVParsed {
+            r#type,
+            client: 1,
+            transaction_id,
+            amount,
+            destination: None,
+        };
+        let result = pos.manage_transaction(&transaction, store, &policy);
+        if let Ok(updated) = result {
+            pos = updated;
+        }
+        result
+    })
+    .collect()
+}
+
+#[test]
+fn dispute_on_a_withdrawal_is_rejected_by_default() {
+    let results = disputed_withdrawal(EnumWithdrawalDispute::Reject, EnumType::Chargeback);
+
+    assert_eq!(Err(EnumRejection::WithdrawalNotDisputable), results[2]);
+    assert_eq!(Err(EnumRejection::DisputeNotOpen), results[3]);
+}
+
+#[test]
+fn disputed_withdrawal_is_held_then_given_back_on_chargeback() {
+    let results = disputed_withdrawal(EnumWithdrawalDispute::Reverse, EnumType::Chargeback);
+
+    assert_eq!(
+        Ok(Position {
+            client: 1,
+            available: Decimal::new(6, 0),
+            held: Decimal::new(4, 0),
+            total: Decimal::new(10, 0),
+            status: EnumStatus::Active,
+        }),
+        results[2]
+    );
+    assert_eq!(
+        Ok(Position {
+            client: 1,
+            available: Decimal::new(10, 0),
+            held: Decimal::zero(),
+            total: Decimal::new(10, 0),
+            status: EnumStatus::Locked,
+        }),
+        results[3]
+    );
+}
+
+#[test]
+fn resolved_withdrawal_dispute_keeps_the_withdrawal() {
+    let results = disputed_withdrawal(EnumWithdrawalDispute::Reverse, EnumType::Resolve);
+
+    assert_eq!(
+        Ok(Position {
+            client: 1,
+            available: Decimal::new(6, 0),
+            held: Decimal::zero(),
+            total: Decimal::new(6, 0),
+            status: EnumStatus::Active,
+        }),
+        results[3]
+    );
+}
+
+// Deposits 10, withdraws 8 and disputes the deposit, then closes the dispute
+fn overdrawn_dispute(
+    negative_balance: EnumNegativeBalance,
+    closing: EnumType,
+) -> Vec<Result<Position, EnumRejection>> {
+    let store = &mut TransactionStore::default();
+    let policy = Policy {
+        negative_balance,
+        ..Default::default()
+    };
+    let mut pos = Position::new(1);
+
+    [
+        (EnumType::Deposit, 1, Some(Decimal::new(10, 0))),
+        (EnumType::Withdrawal, 2, Some(Decimal::new(8, 0))),
+        (EnumType::Dispute, 1, None),
+        (closing, 1, None),
+    ]
+    .into_iter()
+    .map(|(r#type, transaction_id, amount)| {
+        let transaction = CSVParsed {
+            r#type,
+            client: 1,
+            transaction_id,
+            amount,
+            destination: None,
+        };
+        let result = pos.manage_transaction(&transaction, store, &policy);
+        if let Ok(updated) = result {
+            pos = updated;
+        }
+        result
+    })
+    .collect()
+}
+
+fn position(available: i64, held: i64, total: i64, status: EnumStatus) -> Position {
+    Position {
+        client: 1,
+        available: Decimal::new(available, 0),
+        held: Decimal::new(held, 0),
+        total: Decimal::new(total, 0),
+        status,
+    }
+}
+
+#[test]
+fn overdrawn_dispute_goes_negative_by_default() {
+    let results = overdrawn_dispute(EnumNegativeBalance::Allow, EnumType::Chargeback);
+
+    assert_eq!(Ok(position(-8, 10, 2, EnumStatus::Active)), results[2]);
+    assert_eq!(Ok(position(-8, 0, -8, EnumStatus::Locked)), results[3]);
+}
+
+#[test]
+fn overdrawn_dispute_is_rejected() {
+    let results = overdrawn_dispute(EnumNegativeBalance::Reject, EnumType::Chargeback);
+
+    assert_eq!(Err(EnumRejection::InsufficientFunds), results[2]);
+    assert_eq!(Err(EnumRejection::DisputeNotOpen), results[3]);
+}
+
+#[test]
+fn overdrawn_dispute_holds_what_is_available() {
+    let results = overdrawn_dispute(EnumNegativeBalance::HoldAvailable, EnumType::Chargeback);
+    assert_eq!(Ok(position(0, 2, 2, EnumStatus::Active)), results[2]);
+    assert_eq!(Ok(position(-8, 0, -8, EnumStatus::Locked)), results[3]);
+
+    let results = overdrawn_dispute(EnumNegativeBalance::HoldAvailable, EnumType::Resolve);
+    assert_eq!(Ok(position(2, 0, 2, EnumStatus::Active)), results[3]);
+}
+
+fn change_status(pos: Position, r#type: EnumType) -> Result<Position, EnumRejection> {
+    let transaction = CSVParsed {
+        r#type,
+        client: 1,
+        transaction_id: 1,
+        amount: None,
+        destination: None,
+    };
+    pos.manage_transaction(
+        &transaction,
+        &mut TransactionStore::default(),
+        &Policy::default(),
+    )
+}
+
+#[test]
+fn operator_changes_the_account_status() {
+    let locked = position(0, 0, 0, EnumStatus::Locked);
+    let active = change_status(locked, EnumType::Unlock).unwrap();
+    assert_eq!(EnumStatus::Active, active.status);
+
+    let frozen = change_status(active, EnumType::Freeze).unwrap();
+    assert_eq!(EnumStatus::Frozen, frozen.status);
+    assert_eq!(
+        Err(EnumRejection::InvalidStatusChange),
+        change_status(frozen, EnumType::Freeze)
+    );
+
+    let closed = change_status(frozen, EnumType::Close).unwrap();
+    assert_eq!(EnumStatus::Closed, closed.status);
+    assert_eq!(
+        Err(EnumRejection::InvalidStatusChange),
+        change_status(closed, EnumType::Unlock)
+    );
+    assert_eq!(
+        EnumStatus::Active,
+        change_status(closed, EnumType::Reopen).unwrap().status
+    );
+
+    assert_eq!(
+        Err(EnumRejection::AccountNotEmpty),
+        change_status(position(1, 0, 1, EnumStatus::Active), EnumType::Close)
+    );
+}
+
+#[test]
+fn frozen_account_only_refuses_withdrawals() {
+    let store = &mut TransactionStore::default();
+    let frozen = position(5, 0, 5, EnumStatus::Frozen);
+    let transaction = |r#type, transaction_id| CSVParsed {
+        r#type,
+        client: 1,
+        transaction_id,
+        amount: Some(Decimal::new(1, 0)),
+        destination: None,
+    };
+
+    assert_eq!(
+        Err(EnumRejection::AccountFrozen),
+        frozen.manage_transaction(
+            &transaction(EnumType::Withdrawal, 1),
+            store,
+            &Policy::default()
+        )
+    );
+    assert_eq!(
+        Ok(position(6, 0, 6, EnumStatus::Frozen)),
+        frozen.manage_transaction(
+            &transaction(EnumType::Deposit, 2),
+            store,
+            &Policy::default()
+        )
+    );
+    assert_eq!(
+        Err(EnumRejection::AccountClosed),
+        position(0, 0, 0, EnumStatus::Closed).manage_transaction(
+            &transaction(EnumType::Deposit, 3),
+            store,
+            &Policy::default()
+        )
+    );
+}
+
+#[test]
+fn transfer_is_sent_then_received() {
+    let transfer = CSVParsed {
+        r#type: EnumType::Transfer,
+        client: 1,
+        transaction_id: 1,
+        amount: Some(Decimal::new(2, 0)),
+        destination: Some(2),
+    };
+    let send = |pos: Position| {
+        pos.manage_transaction(
+            &transfer,
+            &mut TransactionStore::default(),
+            &Policy::default(),
+        )
+    };
+
+    assert_eq!(
+        Ok(position(3, 0, 3, EnumStatus::Active)),
+        send(position(5, 0, 5, EnumStatus::Active))
+    );
+    assert_eq!(
+        Err(EnumRejection::InsufficientFunds),
+        send(position(1, 0, 1, EnumStatus::Active))
+    );
+    assert_eq!(
+        Err(EnumRejection::AccountFrozen),
+        send(position(5, 0, 5, EnumStatus::Frozen))
+    );
+
+    assert_eq!(
+        Ok(position(3, 1, 4, EnumStatus::Frozen)),
+        position(1, 1, 2, EnumStatus::Frozen).receive(&transfer)
+    );
+    assert_eq!(
+        Err(EnumRejection::DestinationLocked),
+        position(1, 0, 1, EnumStatus::Locked).receive(&transfer)
+    );
+    assert_eq!(
+        Err(EnumRejection::DestinationClosed),
+        position(0, 0, 0, EnumStatus::Closed).receive(&transfer)
+    );
+}
+
+#[test]
+fn dispute_and_chargeback_of_part_of_a_deposit() {
+    let store = &mut TransactionStore::default();
+    let policy = Policy {
+        allow_redispute: true,
+        ..Default::default()
+    };
+    let mut pos = position(0, 0, 0, EnumStatus::Active);
+
+    let results: Vec<_> = [
+        (EnumType::Deposit, Some(10)),
+        (EnumType::Dispute, Some(11)),
+        (EnumType::Dispute, Some(4)),
+        (EnumType::Resolve, Some(5)),
+        (EnumType::Resolve, Some(1)),
+        (EnumType::Chargeback, Some(2)),
+        (EnumType::Resolve, None),
+        (EnumType::Dispute, None),
+        (EnumType::Unlock, None),
+        (EnumType::Dispute, Some(9)),
+        (EnumType::Dispute, None),
+    ]
+    .into_iter()
+    .map(|(r#type, amount)| {
+        let transaction = CSVParsed {
+            r#type,
+            client: 1,
+            transaction_id: 1,
+            amount: amount.map(|amount| Decimal::new(amount, 0)),
+            destination: None,
+        };
+        let result = pos.manage_transaction(&transaction, store, &policy);
+        if let Ok(updated) = result {
+            pos = updated;
+        }
+        result
+    })
+    .collect();
+
+    assert_eq!(Err(EnumRejection::ExceedsTransaction), results[1]);
+    assert_eq!(Ok(position(6, 4, 10, EnumStatus::Active)), results[2]);
+    assert_eq!(Err(EnumRejection::ExceedsDispute), results[3]);
+    assert_eq!(Ok(position(7, 3, 10, EnumStatus::Active)), results[4]);
+    assert_eq!(Ok(position(7, 1, 8, EnumStatus::Locked)), results[5]);
+    // The rest of the open dispute is settled on the locked account, a new dispute is not
+    assert_eq!(Ok(position(8, 0, 8, EnumStatus::Locked)), results[6]);
+    assert_eq!(Err(EnumRejection::AccountLocked), results[7]);
+    // The 2 charged back cannot be disputed again
+    assert_eq!(Err(EnumRejection::ExceedsTransaction), results[9]);
+    assert_eq!(Ok(position(0, 8, 8, EnumStatus::Active)), results[10]);
+}
+
+#[test]
+fn rest_of_a_partly_settled_deposit_can_be_disputed() {
+    let store = &mut TransactionStore::default();
+    let policy = Policy::default();
+    let mut pos = position(0, 0, 0, EnumStatus::Active);
+
+    let results: Vec<_> = [
+        (EnumType::Deposit, Some(10)),
+        (EnumType::Dispute, Some(4)),
+        (EnumType::Resolve, None),
+        (EnumType::Dispute, Some(7)),
+        (EnumType::Dispute, Some(5)),
+        (EnumType::Chargeback, None),
+        (EnumType::Unlock, None),
+        (EnumType::Dispute, None),
+        (EnumType::Resolve, None),
+        (EnumType::Dispute, None),
+    ]
+    .into_iter()
+    .map(|(r#type, amount)| {
+        let transaction = CSVParsed {
+            r#type,
+            client: 1,
+            transaction_id: 1,
+            amount: amount.map(|amount| Decimal::new(amount, 0)),
+            destination: None,
+        };
+        let result = pos.manage_transaction(&transaction, store, &policy);
+        if let Ok(updated) = result {
+            pos = updated;
+        }
+        result
+    })
+    .collect();
+
+    // The 4 resolved are not disputable again, the other 6 still are
+    assert_eq!(Ok(position(10, 0, 10, EnumStatus::Active)), results[2]);
+    assert_eq!(Err(EnumRejection::ExceedsTransaction), results[3]);
+    assert_eq!(Ok(position(5, 5, 10, EnumStatus::Active)), results[4]);
+    // After the chargeback of 5, the last 1 can be disputed
+    assert_eq!(Ok(position(5, 0, 5, EnumStatus::Locked)), results[5]);
+    assert_eq!(Ok(position(4, 1, 5, EnumStatus::Active)), results[7]);
+    assert_eq!(Ok(position(5, 0, 5, EnumStatus::Active)), results[8]);
+    assert_eq!(Err(EnumRejection::DisputeClosed), results[9]);
 }
@@ -0,0 +1,51 @@
+use crate::cli::{Cli, Command};
+use clap::{CommandFactory, Parser};
+use rustling::parser::{EnumFormat, EnumInputFormat};
+use rustling::EnumOrder;
+
+#[test]
+fn cli_is_well_formed() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn arguments_without_subcommand_are_the_process_ones() {
+    let cli = Cli::try_parse_from(["rustling", "a.csv", "b.csv", "--format", "json"]).unwrap();
+
+    assert!(cli.command.is_none());
+    assert_eq!(vec!["a.csv", "b.csv"], cli.process.input.inputs);
+    assert_eq!(EnumFormat::Json, cli.process.format);
+    assert_eq!(EnumOrder::Client, cli.process.order);
+}
+
+#[test]
+fn subcommands_share_the_input_flags() {
+    let cli = Cli::try_parse_from([
+        "rustling",
+        "replay",
+        "--client",
+        "7",
+        "--input-format",
+        "jsonl",
+        "--lenient",
+        "-",
+    ])
+    .unwrap();
+
+    match cli.command {
+        Some(Command::Replay(args)) => {
+            assert_eq!(7, args.client);
+            assert_eq!(Some(EnumInputFormat::Jsonl), args.input.input_format);
+            assert!(args.input.lenient);
+            assert_eq!(vec!["-"], args.input.inputs);
+        }
+        _ => panic!("expected replay"),
+    }
+}
+
+#[test]
+fn invalid_values_are_refused() {
+    assert!(Cli::try_parse_from(["rustling", "process", "--format", "xml", "a.csv"]).is_err());
+    assert!(Cli::try_parse_from(["rustling", "audit", "--format", "json", "a.csv"]).is_err());
+    assert!(Cli::try_parse_from(["rustling", "replay", "a.csv"]).is_err());
+}
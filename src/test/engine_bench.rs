@@ -0,0 +1,59 @@
+// Run with `cargo test --release -- --ignored --nocapture engine_bench`
+use crate::engine::{Engine, EnumOrder};
+use crate::{CSVParsed, EnumType};
+use rust_decimal::Decimal;
+use std::time::{Duration, Instant};
+
+pub(crate) const CLIENTS: u32 = 10_000;
+
+pub(crate) fn transaction(row: u32) -> CSVParsed {
+    let client = (row % CLIENTS) as u16;
+    // Every client deposits, withdraws and then opens and resolves a dispute on its deposit
+    let (r#type, transaction_id, amount) = match (row / CLIENTS) % 5 {
+        0 => (EnumType::Deposit, row, Some(Decimal::new(1000, 2))),
+        1 => (EnumType::Withdrawal, row, Some(Decimal::new(100, 2))),
+        2 => (EnumType::Dispute, row - 2 * CLIENTS, None),
+        3 => (EnumType::Resolve, row - 3 * CLIENTS, None),
+        _ => (EnumType::Deposit, row, Some(Decimal::new(1, 2))),
+    };
+
+    CSVParsed {
+        r#type,
+        client,
+        transaction_id,
+        amount,
+        destination: None,
+    }
+}
+
+fn run(rows: u32) -> Duration {
+    let start = Instant::now();
+
+    let mut engine = Engine::default();
+    for row in 0..rows {
+        let _ = engine.process(&transaction(row));
+    }
+    assert_eq!(
+        CLIENTS as usize,
+        engine.into_positions(EnumOrder::Client).len()
+    );
+
+    let elapsed = start.elapsed();
+    println!(
+        "{:>9} rows in {:>8.3?} ({:.0} rows/s)",
+        rows,
+        elapsed,
+        rows as f64 / elapsed.as_secs_f64()
+    );
+    elapsed
+}
+
+#[test]
+#[ignore]
+fn engine_scales_linearly_with_rows() {
+    let small = run(1_000_000);
+    let large = run(4_000_000);
+
+    // A quadratic engine would take ~16 times longer on four times the rows
+    assert!(large < small * 8, "{:?} vs {:?}", large, small);
+}
@@ -0,0 +1,409 @@
+use crate::calculation::{EnumNegativeBalance, EnumRejection, EnumStatus, Policy, Position};
+use crate::engine::{Engine, EnumOrder, Exposure};
+use crate::parser::{
+    read_positions, read_transactions, CSVParsed, EnumInputFormat, EnumPrecision, EnumType,
+};
+use rust_decimal::prelude::Zero;
+use rust_decimal::Decimal;
+
+fn run(input: &str) -> Vec<Position> {
+    let mut engine = Engine::default();
+    for transaction in read_transactions(
+        input.as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    ) {
+        let _ = engine.process(&transaction.unwrap());
+    }
+    engine.into_positions(EnumOrder::Client)
+}
+
+#[test]
+fn positions_are_computed_row_by_row() {
+    let positions = run("type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.0
+deposit,1,3,2.0
+withdrawal,1,4,1.5
+withdrawal,2,5,3.0
+");
+
+    assert_eq!(
+        vec![
+            Position {
+                client: 1,
+                available: Decimal::new(15, 1),
+                held: Decimal::zero(),
+                total: Decimal::new(15, 1),
+                status: EnumStatus::Active,
+            },
+            Position {
+                client: 2,
+                available: Decimal::new(2, 0),
+                held: Decimal::zero(),
+                total: Decimal::new(2, 0),
+                status: EnumStatus::Active,
+            },
+        ],
+        positions
+    );
+}
+
+#[test]
+fn dispute_refers_to_an_earlier_row() {
+    let positions = run("type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2,2.0
+dispute,1,1,
+chargeback,1,1,
+deposit,1,3,5.0
+");
+
+    assert_eq!(
+        vec![Position {
+            client: 1,
+            available: Decimal::new(2, 0),
+            held: Decimal::zero(),
+            total: Decimal::new(2, 0),
+            status: EnumStatus::Locked,
+        }],
+        positions
+    );
+}
+
+#[test]
+fn dispute_before_the_deposit_is_ignored() {
+    let positions = run("type,client,tx,amount
+dispute,1,1,
+deposit,1,1,1.0
+");
+
+    assert_eq!(
+        vec![Position {
+            client: 1,
+            available: Decimal::new(1, 0),
+            held: Decimal::zero(),
+            total: Decimal::new(1, 0),
+            status: EnumStatus::Active,
+        }],
+        positions
+    );
+}
+
+#[test]
+fn dispute_on_a_transaction_of_another_client_is_ignored() {
+    let positions = run("type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.0
+dispute,2,1,
+");
+
+    assert_eq!(
+        vec![
+            Position {
+                client: 1,
+                available: Decimal::new(1, 0),
+                held: Decimal::zero(),
+                total: Decimal::new(1, 0),
+                status: EnumStatus::Active,
+            },
+            Position {
+                client: 2,
+                available: Decimal::new(2, 0),
+                held: Decimal::zero(),
+                total: Decimal::new(2, 0),
+                status: EnumStatus::Active,
+            },
+        ],
+        positions
+    );
+}
+
+#[test]
+fn duplicate_deposit_is_refused() {
+    let positions = run("type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,1,2.0
+deposit,1,1,3.0
+dispute,1,1,
+");
+
+    assert_eq!(
+        vec![
+            Position {
+                client: 1,
+                available: Decimal::zero(),
+                held: Decimal::new(1, 0),
+                total: Decimal::new(1, 0),
+                status: EnumStatus::Active,
+            },
+            Position {
+                client: 2,
+                available: Decimal::zero(),
+                held: Decimal::zero(),
+                total: Decimal::zero(),
+                status: EnumStatus::Active,
+            },
+        ],
+        positions
+    );
+}
+
+#[test]
+fn positions_in_order_of_appearance() {
+    let mut engine = Engine::default();
+    for transaction in read_transactions(
+        "type,client,tx,amount
+deposit,3,1,1.0
+deposit,1,2,1.0
+deposit,2,3,1.0
+deposit,1,4,1.0
+"
+        .as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    ) {
+        let _ = engine.process(&transaction.unwrap());
+    }
+
+    assert_eq!(engine.snapshot().positions, engine.positions());
+    assert_eq!(
+        vec![3, 1, 2],
+        engine
+            .into_positions(EnumOrder::Appearance)
+            .iter()
+            .map(|position| position.client)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn transactions_apply_on_top_of_opening_positions() {
+    let mut engine = Engine::default();
+    for position in read_positions(
+        "client,available,held,total,locked
+2,1.0,0.5,1.5,false
+1,0,0,0,true
+"
+        .as_bytes(),
+    ) {
+        engine.open(position.unwrap()).unwrap();
+    }
+    assert_eq!(65, engine.open(Position::new(1)).unwrap_err().exit_code());
+
+    for transaction in read_transactions(
+        "type,client,tx,amount
+withdrawal,2,1,0.4
+deposit,1,2,1.0
+"
+        .as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    ) {
+        let _ = engine.process(&transaction.unwrap());
+    }
+
+    assert_eq!(
+        vec![
+            Position {
+                client: 2,
+                available: Decimal::new(6, 1),
+                held: Decimal::new(5, 1),
+                total: Decimal::new(11, 1),
+                status: EnumStatus::Active,
+            },
+            Position {
+                status: EnumStatus::Locked,
+                ..Position::new(1)
+            },
+        ],
+        engine.into_positions(EnumOrder::Appearance)
+    );
+}
+
+#[test]
+fn clients_with_uncovered_disputes_are_exposed() {
+    let mut engine = Engine::with_policy(Policy {
+        negative_balance: EnumNegativeBalance::HoldAvailable,
+        ..Default::default()
+    });
+    for transaction in read_transactions(
+        "type,client,tx,amount
+deposit,1,1,10.0
+withdrawal,1,2,8.0
+dispute,1,1,
+deposit,2,3,5.0
+dispute,2,3,
+deposit,3,4,5.0
+withdrawal,3,5,4.0
+dispute,3,4,
+chargeback,3,4,
+"
+        .as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    ) {
+        engine.process(&transaction.unwrap()).unwrap();
+    }
+
+    assert_eq!(
+        vec![
+            Exposure {
+                client: 1,
+                available: Decimal::zero(),
+                shortfall: Decimal::new(80, 1),
+            },
+            Exposure {
+                client: 3,
+                available: Decimal::new(-40, 1),
+                shortfall: Decimal::zero(),
+            },
+        ],
+        engine.exposures()
+    );
+}
+
+#[test]
+fn status_changes_need_the_operator_inputs() {
+    let mut engine = Engine::default();
+    let rows: Vec<_> = read_transactions(
+        "type,client,tx,amount
+deposit,1,1,1.0
+dispute,1,1,
+chargeback,1,1,
+unlock,1,2,
+deposit,1,3,1.0
+"
+        .as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+
+    let results: Vec<_> = rows
+        .iter()
+        .map(|transaction| engine.process(transaction))
+        .collect();
+    assert_eq!(Err(EnumRejection::NotAuthorised), results[3]);
+    assert_eq!(Err(EnumRejection::AccountLocked), results[4]);
+
+    assert_eq!(Ok(()), engine.process_admin(&rows[3]));
+    assert_eq!(
+        Ok(()),
+        engine.process(&CSVParsed {
+            transaction_id: 4,
+            ..rows[4].clone()
+        })
+    );
+    assert_eq!(
+        Some(EnumStatus::Active),
+        engine.position(1).map(|position| position.status)
+    );
+}
+
+#[test]
+fn status_changes_need_an_existing_account() {
+    let mut engine = Engine::default();
+    let rows: Vec<_> = read_transactions(
+        "type,client,tx,amount
+close,9,1,
+unlock,8,2,
+"
+        .as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+
+    assert_eq!(
+        Err(EnumRejection::UnknownClient),
+        engine.process_admin(&rows[0])
+    );
+    assert_eq!(
+        Err(EnumRejection::UnknownClient),
+        engine.process_admin(&rows[1])
+    );
+    assert_eq!(Err(EnumRejection::NotAuthorised), engine.process(&rows[1]));
+    assert!(engine.positions().is_empty());
+}
+
+const TRANSFERS: &str = "type,client,tx,amount,destination
+deposit,1,1,5.0,
+deposit,3,2,1.0,
+dispute,3,2,,
+chargeback,3,2,,
+transfer,1,3,2.0,2
+transfer,1,4,9.0,2
+transfer,1,5,1.0,3
+transfer,3,6,1.0,1
+transfer,1,3,1.0,2
+transfer,1,7,9.0,4
+";
+
+#[test]
+fn transfer_moves_funds_between_clients() {
+    let mut engine = Engine::default();
+    let results: Vec<_> = read_transactions(
+        TRANSFERS.as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .map(|transaction| engine.process(&transaction.unwrap()))
+    .collect();
+
+    assert_eq!(
+        vec![
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(EnumRejection::InsufficientFunds),
+            Err(EnumRejection::DestinationLocked),
+            Err(EnumRejection::AccountLocked),
+            Err(EnumRejection::DuplicateTransaction),
+            Err(EnumRejection::InsufficientFunds),
+        ],
+        results
+    );
+    // Client 4 never received anything, so it has no account
+    assert_eq!(
+        vec![
+            (1, Decimal::new(3, 0)),
+            (3, Decimal::zero()),
+            (2, Decimal::new(2, 0)),
+        ],
+        engine
+            .into_positions(EnumOrder::Appearance)
+            .into_iter()
+            .map(|position| (position.client, position.total))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn transfer_to_the_same_client_is_rejected() {
+    let mut engine = Engine::default();
+    let transaction = |r#type, transaction_id, amount, destination| CSVParsed {
+        r#type,
+        client: 1,
+        transaction_id,
+        amount: Some(Decimal::new(amount, 0)),
+        destination,
+    };
+
+    assert_eq!(
+        Ok(()),
+        engine.process(&transaction(EnumType::Deposit, 1, 10, None))
+    );
+    assert_eq!(
+        Err(EnumRejection::SelfTransfer),
+        engine.process(&transaction(EnumType::Transfer, 2, 4, Some(1)))
+    );
+    assert_eq!(
+        Some(Decimal::new(10, 0)),
+        engine.position(1).map(|position| position.total)
+    );
+}
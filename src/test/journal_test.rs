@@ -0,0 +1,114 @@
+use crate::engine::{Engine, EnumOrder};
+use crate::journal::{Input, Journal};
+use crate::parser::{read_transactions, EnumInputFormat, EnumPrecision};
+use crate::EnumError;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+const INPUT: &str = "type,client,tx,amount
+deposit,1,1,5.0
+deposit,2,2,3.0
+withdrawal,1,3,1.0
+dispute,1,1,
+deposit,2,4,1.0
+deposit,2,4,1.0
+resolve,1,1,
+chargeback,2,2,
+dispute,2,2,
+chargeback,2,2,
+deposit,1,5,2.5
+";
+
+fn inputs(size: u64) -> Vec<Input> {
+    vec![Input {
+        name: "input.csv".to_string(),
+        size: Some(size),
+    }]
+}
+
+// Applies the rows after `skip` and stops after `crash_after` of them, as a killed run would
+fn run(journal_file: &str, crash_after: Option<usize>) -> (Engine, usize) {
+    let mut engine = Engine::default();
+    let mut journal = Journal::open(journal_file, inputs(1), 3, &mut engine).unwrap();
+    let skip = journal.applied();
+
+    for (row, transaction) in read_transactions(
+        INPUT.as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .enumerate()
+    .skip(skip)
+    .take(crash_after.unwrap_or(usize::MAX))
+    {
+        let transaction = transaction.unwrap();
+        let _ = engine.process(&transaction);
+        journal.record(row + 1, &transaction, &engine).unwrap();
+    }
+
+    if crash_after.is_none() {
+        journal.commit(&engine).unwrap();
+        journal.finish().unwrap();
+    }
+    (engine, skip)
+}
+
+#[test]
+fn interrupted_run_resumes_at_the_last_journaled_row() {
+    let dir = std::env::temp_dir().join(format!("rustling-journal-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let journal_file = dir.join("run.journal").to_string_lossy().into_owned();
+
+    let (expected, _) = run(&dir.join("reference.journal").to_string_lossy(), None);
+
+    // Killed after seven rows, one past the last checkpoint, while writing the eighth
+    run(&journal_file, Some(7));
+    // The state when the journal was opened, then the changes of each checkpoint
+    assert_eq!(
+        3,
+        std::fs::read_to_string(format!("{}.checkpoint", journal_file))
+            .unwrap()
+            .lines()
+            .count()
+    );
+    OpenOptions::new()
+        .append(true)
+        .open(&journal_file)
+        .unwrap()
+        .write_all(br#"{"row":8,"type":"charge"#)
+        .unwrap();
+
+    let (resumed, skip) = run(&journal_file, None);
+
+    assert_eq!(7, skip);
+    assert_eq!(
+        expected.into_positions(EnumOrder::Client),
+        resumed.into_positions(EnumOrder::Client)
+    );
+    // A finished run leaves nothing to resume
+    assert!(!std::path::Path::new(&journal_file).exists());
+    assert_eq!(0, run(&journal_file, None).1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn journal_of_other_inputs_is_not_resumed() {
+    let dir = std::env::temp_dir().join(format!("rustling-stale-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let journal_file = dir.join("run.journal").to_string_lossy().into_owned();
+
+    run(&journal_file, Some(4));
+
+    let resumed = Journal::open(&journal_file, inputs(2), 3, &mut Engine::default());
+    assert!(matches!(resumed, Err(EnumError::JournalMismatch(_))));
+    let from_stdin = Journal::open(
+        &journal_file,
+        vec![Input::new("-")],
+        3,
+        &mut Engine::default(),
+    );
+    assert!(matches!(from_stdin, Err(EnumError::JournalMismatch(_))));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
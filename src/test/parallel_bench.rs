@@ -0,0 +1,47 @@
+// Run with `cargo test --release -- --ignored --nocapture parallel_bench`
+use crate::engine::engine_bench::{transaction, CLIENTS};
+use crate::engine::{Engine, EnumOrder};
+use crate::parallel::process;
+use std::time::{Duration, Instant};
+
+const ROWS: u32 = 4_000_000;
+
+fn run(threads: usize) -> Duration {
+    let start = Instant::now();
+
+    let mut engine = Engine::default();
+    if threads == 1 {
+        for row in 0..ROWS {
+            let _ = engine.process(&transaction(row));
+        }
+    } else {
+        process(
+            &mut engine,
+            threads,
+            (0..ROWS).map(|row| Ok((row as usize + 1, transaction(row)))),
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        CLIENTS as usize,
+        engine.into_positions(EnumOrder::Client).len()
+    );
+
+    let elapsed = start.elapsed();
+    println!(
+        "{:>2} threads: {:>9} rows in {:>8.3?} ({:.0} rows/s)",
+        threads,
+        ROWS,
+        elapsed,
+        ROWS as f64 / elapsed.as_secs_f64()
+    );
+    elapsed
+}
+
+#[test]
+#[ignore]
+fn sharded_engine_against_the_serial_one() {
+    for threads in [1, 2, 4, 8] {
+        run(threads);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::calculation::EnumRejection;
+use crate::engine::{Engine, EnumOrder};
+use crate::parallel::process;
+use crate::parser::{read_transactions, EnumInputFormat, EnumPrecision};
+
+const INPUT: &str = "type,client,tx,amount
+deposit,3,1,5.0
+deposit,1,2,3.0
+deposit,2,2,3.0
+withdrawal,3,3,1.0
+dispute,3,1,
+deposit,4,4,1.0
+withdrawal,1,5,4.0
+chargeback,3,1,
+deposit,3,6,1.0
+dispute,4,2,
+";
+
+fn rows() -> impl Iterator<Item = Result<(usize, crate::CSVParsed), crate::EnumError>> {
+    rows_of(INPUT)
+}
+
+fn rows_of(
+    input: &str,
+) -> impl Iterator<Item = Result<(usize, crate::CSVParsed), crate::EnumError>> + '_ {
+    read_transactions(
+        input.as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .enumerate()
+    .map(|(row, transaction)| transaction.map(|transaction| (row + 1, transaction)))
+}
+
+fn matches_the_serial_run(input: &str) -> Vec<(usize, EnumRejection)> {
+    let mut serial = Engine::default();
+    let mut expected = vec![];
+    for row in rows_of(input) {
+        let (row, transaction) = row.unwrap();
+        if let Err(rejection) = serial.process(&transaction) {
+            expected.push((row, rejection));
+        }
+    }
+
+    for threads in 1..=4 {
+        let mut engine = Engine::default();
+        let rejected = process(&mut engine, threads, rows_of(input)).unwrap();
+
+        assert_eq!(
+            expected,
+            rejected
+                .into_iter()
+                .map(|rejected| (rejected.row, rejected.rejection))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            serial.snapshot().positions,
+            engine.into_positions(EnumOrder::Appearance)
+        );
+    }
+    expected
+}
+
+#[test]
+fn sharded_run_matches_the_serial_one() {
+    let expected = matches_the_serial_run(INPUT);
+
+    // The id 2 of client 2 is a duplicate even though client 1 is on another shard
+    assert_eq!((3, EnumRejection::DuplicateTransaction), expected[0]);
+}
+
+#[test]
+fn state_of_an_earlier_run_is_sharded() {
+    let mut engine = Engine::default();
+    process(&mut engine, 3, rows().take(5)).unwrap();
+    let rejected = process(&mut engine, 2, rows().skip(5)).unwrap();
+
+    assert_eq!(
+        vec![
+            (7, EnumRejection::InsufficientFunds),
+            (9, EnumRejection::AccountLocked),
+            (10, EnumRejection::WrongClient)
+        ],
+        rejected
+            .into_iter()
+            .map(|rejected| (rejected.row, rejected.rejection))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn transfers_across_shards_match_the_serial_run() {
+    // Client 3 is locked, so the transfer to it is refunded to client 1
+    let expected = matches_the_serial_run(
+        "type,client,tx,amount,destination
+deposit,1,1,5.0,
+deposit,3,2,1.0,
+dispute,3,2,,
+chargeback,3,2,,
+transfer,1,3,2.0,2
+deposit,2,4,1.0,
+transfer,1,5,1.0,3
+transfer,2,6,2.5,4
+withdrawal,1,7,2.5,
+transfer,4,8,9.0,1
+transfer,1,9,9.0,5
+transfer,3,10,1.0,6
+",
+    );
+
+    // Clients 5 and 6 only appear in rejected transfers, so neither run adds them
+    assert_eq!(
+        vec![
+            (7, EnumRejection::DestinationLocked),
+            (10, EnumRejection::InsufficientFunds),
+            (11, EnumRejection::InsufficientFunds),
+            (12, EnumRejection::AccountLocked)
+        ],
+        expected
+    );
+}
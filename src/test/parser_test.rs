@@ -0,0 +1,529 @@
+use crate::calculation::{EnumStatus, Position};
+use crate::parser::{
+    expand_inputs, read_inputs, read_positions, read_transactions, write_out_positions, CSVOutput,
+    EnumFormat, EnumInputFormat, EnumPrecision,
+};
+use crate::{EnumError, EnumType};
+use rust_decimal::prelude::Zero;
+use rust_decimal::Decimal;
+
+fn first_error(input: &str) -> EnumError {
+    read_transactions(
+        input.as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .find_map(Result::err)
+    .unwrap()
+}
+
+#[test]
+fn rows_are_parsed() {
+    let transactions: Vec<_> = read_transactions(
+        "type, client, tx, amount
+deposit, 1, 1, 1.5
+dispute, 1, 1,
+"
+        .as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+
+    assert_eq!(2, transactions.len());
+    assert_eq!(EnumType::Deposit, transactions[0].r#type);
+    assert_eq!(Some(Decimal::new(15, 1)), transactions[0].amount);
+    assert_eq!(EnumType::Dispute, transactions[1].r#type);
+    assert_eq!(None, transactions[1].amount);
+}
+
+#[test]
+fn unparsable_amount_reports_line_column_and_value() {
+    let err = first_error(
+        "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2,abc
+",
+    );
+
+    assert_eq!("line 3: cannot convert amount 'abc'", err.to_string());
+    assert_eq!(65, err.exit_code());
+}
+
+#[test]
+fn unknown_type_reports_its_value() {
+    let err = first_error(
+        "type,client,tx,amount
+payout,1,1,1.0
+",
+    );
+
+    assert_eq!("line 2: unknown transaction type 'payout'", err.to_string());
+}
+
+#[test]
+fn missing_field_reports_the_line() {
+    let err = first_error(
+        "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2
+",
+    );
+
+    assert_eq!(
+        "line 3: invalid csv: expected 4 fields, found 3",
+        err.to_string()
+    );
+}
+
+#[test]
+fn error_in_a_file_keeps_its_exit_code() {
+    let err = EnumError::InFile(
+        "input.csv".to_string(),
+        Box::new(EnumError::CannotConvert {
+            line: 2,
+            column: "client".to_string(),
+            value: "-1".to_string(),
+        }),
+    );
+
+    assert_eq!(
+        "input.csv: line 2: cannot convert client '-1'",
+        err.to_string()
+    );
+    assert_eq!(65, err.exit_code());
+}
+
+#[test]
+fn rows_after_a_malformed_row_are_still_read() {
+    let results: Vec<_> = read_transactions(
+        "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2,abc
+withdrawal,1
+deposit,2,3,2.0
+"
+        .as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .collect();
+
+    assert_eq!(4, results.len());
+    assert!(results[0].is_ok());
+    assert!(results[1].as_ref().is_err_and(EnumError::is_malformed_row));
+    assert!(results[2].as_ref().is_err_and(EnumError::is_malformed_row));
+    assert_eq!(3, results[3].as_ref().unwrap().transaction_id);
+}
+
+#[test]
+fn unreadable_input_is_not_a_malformed_row() {
+    assert!(!EnumError::CannotReadInput("broken pipe".to_string()).is_malformed_row());
+    assert!(!EnumError::FileNotPresent("input.csv".to_string()).is_malformed_row());
+}
+
+fn amount(input: &str, precision: EnumPrecision) -> Result<Option<Decimal>, String> {
+    read_transactions(
+        format!("type,client,tx,amount\n{}\n", input).as_bytes(),
+        EnumInputFormat::Csv,
+        precision,
+    )
+    .next()
+    .unwrap()
+    .map(|transaction| transaction.amount)
+    .map_err(|err| err.to_string())
+}
+
+#[test]
+fn negative_and_zero_amounts_are_rejected() {
+    assert_eq!(
+        Err("line 2: invalid amount '-1.0': must be positive".to_string()),
+        amount("deposit,1,1,-1.0", EnumPrecision::Reject)
+    );
+    assert_eq!(
+        Err("line 2: invalid amount '0': must be positive".to_string()),
+        amount("withdrawal,1,1,0", EnumPrecision::Reject)
+    );
+}
+
+#[test]
+fn amounts_with_more_than_four_decimals() {
+    assert_eq!(
+        Err("line 2: invalid amount '1.123456789': more than 4 decimal places".to_string()),
+        amount("deposit,1,1,1.123456789", EnumPrecision::Reject)
+    );
+    assert_eq!(
+        Ok(Some(Decimal::new(15, 1))),
+        amount("deposit,1,1,1.500000", EnumPrecision::Reject)
+    );
+    assert_eq!(
+        Ok(Some(Decimal::new(11235, 4))),
+        amount("deposit,1,1,1.123456789", EnumPrecision::Round)
+    );
+    assert_eq!(
+        Err("line 2: invalid amount '0.00001': must be positive".to_string()),
+        amount("deposit,1,1,0.00001", EnumPrecision::Round)
+    );
+}
+
+#[test]
+fn amount_presence_depends_on_the_type() {
+    assert_eq!(
+        Err("line 2: invalid amount '': required on a withdrawal".to_string()),
+        amount("withdrawal,1,1,", EnumPrecision::Reject)
+    );
+    assert_eq!(
+        Ok(Some(Decimal::new(10, 1))),
+        amount("dispute,1,1,1.0", EnumPrecision::Reject)
+    );
+    assert_eq!(
+        Err("line 2: invalid amount '-1.0': must be positive".to_string()),
+        amount("resolve,1,1,-1.0", EnumPrecision::Reject)
+    );
+
+    assert_eq!(Ok(None), amount("chargeback,1,1,", EnumPrecision::Reject));
+}
+
+fn destination(input: &str) -> Result<Option<u16>, String> {
+    read_transactions(
+        format!("type,client,tx,amount,destination\n{}\n", input).as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .next()
+    .unwrap()
+    .map(|transaction| transaction.destination)
+    .map_err(|err| err.to_string())
+}
+
+#[test]
+fn destination_is_only_on_transfers() {
+    assert_eq!(Ok(Some(2)), destination("transfer,1,1,1.0,2"));
+    assert_eq!(Ok(None), destination("deposit,1,1,1.0,"));
+    assert_eq!(
+        Err("line 2: invalid destination '': required on a transfer".to_string()),
+        destination("transfer,1,1,1.0,")
+    );
+    assert_eq!(
+        Err("line 2: invalid destination '1': same as the client".to_string()),
+        destination("transfer,1,1,1.0,1")
+    );
+    assert_eq!(
+        Err("line 2: invalid destination '2': not allowed on a deposit".to_string()),
+        destination("deposit,1,1,1.0,2")
+    );
+    assert_eq!(
+        Err("line 2: invalid amount '': required on a transfer".to_string()),
+        destination("transfer,1,1,,2")
+    );
+}
+
+fn write_as(format: EnumFormat, positions: Vec<Position>) -> String {
+    let mut buffer = vec![];
+    write_out_positions(
+        &mut buffer,
+        format,
+        positions.into_iter().map(CSVOutput::from),
+    )
+    .unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+
+fn write(positions: Vec<Position>) -> String {
+    write_as(EnumFormat::Csv, positions)
+}
+
+#[test]
+fn output_amounts_have_four_decimals() {
+    assert_eq!(
+        "client,available,held,total,locked,status
+1,1.5000,1.5000,0.0000,false,active
+",
+        write(vec![Position {
+            client: 1,
+            available: Decimal::new(15, 1),
+            held: Decimal::new(15000, 4),
+            total: -Decimal::new(0, 2),
+            status: EnumStatus::Active,
+        }])
+    );
+}
+
+#[test]
+fn output_without_positions_has_a_header() {
+    assert_eq!("client,available,held,total,locked,status\n", write(vec![]));
+}
+
+#[test]
+fn output_write_failure_is_reported() {
+    struct Broken;
+
+    impl std::io::Write for Broken {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    let err = write_out_positions(
+        Broken,
+        EnumFormat::Csv,
+        vec![Position::new(1)].into_iter().map(CSVOutput::from),
+    )
+    .unwrap_err();
+
+    assert_eq!(74, err.exit_code());
+}
+
+fn two_positions() -> Vec<Position> {
+    vec![
+        Position {
+            client: 1,
+            available: Decimal::new(15, 1),
+            held: Decimal::zero(),
+            total: Decimal::new(15, 1),
+            status: EnumStatus::Active,
+        },
+        Position {
+            client: 2,
+            available: Decimal::zero(),
+            held: Decimal::zero(),
+            total: Decimal::zero(),
+            status: EnumStatus::Locked,
+        },
+    ]
+}
+
+#[test]
+fn output_as_json_array() {
+    assert_eq!(
+        r#"[
+{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false,"status":"active"},
+{"client":2,"available":"0.0000","held":"0.0000","total":"0.0000","locked":true,"status":"locked"}
+]
+"#,
+        write_as(EnumFormat::Json, two_positions())
+    );
+    assert_eq!("[\n]\n", write_as(EnumFormat::Json, vec![]));
+}
+
+#[test]
+fn output_as_json_lines() {
+    assert_eq!(
+        r#"{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false,"status":"active"}
+{"client":2,"available":"0.0000","held":"0.0000","total":"0.0000","locked":true,"status":"locked"}
+"#,
+        write_as(EnumFormat::Jsonl, two_positions())
+    );
+}
+
+#[test]
+fn json_numbers_keep_every_digit() {
+    let transaction = read_transactions(
+        r#"{"type":"deposit","client":1,"tx":1,"amount":12345678901234.5678}"#.as_bytes(),
+        EnumInputFormat::Jsonl,
+        EnumPrecision::Reject,
+    )
+    .next()
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+        Some(Decimal::new(123456789012345678, 4)),
+        transaction.amount
+    );
+}
+
+#[test]
+fn json_lines_are_parsed_like_csv_rows() {
+    let results: Vec<_> = read_transactions(
+        r#"{"type":"deposit","client":1,"tx":1,"amount":"1.5"}
+
+{"type":"deposit","client":1,"tx":2,"amount":2.25}
+{"type":"dispute","client":1,"tx":1}
+{"type":"deposit","client":-1,"tx":3,"amount":"1"}
+{"type":"deposit"
+"#
+        .as_bytes(),
+        EnumInputFormat::Jsonl,
+        EnumPrecision::Reject,
+    )
+    .collect();
+
+    assert_eq!(5, results.len());
+    assert_eq!(
+        Some(Decimal::new(15, 1)),
+        results[0].as_ref().unwrap().amount
+    );
+    assert_eq!(
+        Some(Decimal::new(225, 2)),
+        results[1].as_ref().unwrap().amount
+    );
+    assert_eq!(None, results[2].as_ref().unwrap().amount);
+    assert_eq!(
+        "line 5: cannot convert client '-1'",
+        results[3].as_ref().unwrap_err().to_string()
+    );
+    assert!(results[4].as_ref().is_err_and(EnumError::is_malformed_row));
+}
+
+#[test]
+fn input_format_from_extension() {
+    assert_eq!(
+        EnumInputFormat::Jsonl,
+        EnumInputFormat::from_extension("a.jsonl")
+    );
+    assert_eq!(
+        EnumInputFormat::Binary,
+        EnumInputFormat::from_extension("dir/a.bin")
+    );
+    assert_eq!(
+        EnumInputFormat::Csv,
+        EnumInputFormat::from_extension("a.csv")
+    );
+    assert_eq!(EnumInputFormat::Csv, EnumInputFormat::from_extension("a"));
+}
+
+fn shards(name: &str, shards: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustling-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file_name, contents) in shards {
+        std::fs::write(dir.join(file_name), contents).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn inputs_are_read_in_sequence() {
+    let dir = shards(
+        "sequence",
+        &[
+            ("day-2.csv", "type,client,tx,amount\ndeposit,1,2,2.0\n"),
+            ("day-1.csv", "type,client,tx,amount\ndeposit,1,1,1.0\n"),
+            (
+                "day-3.jsonl",
+                "{\"type\":\"dispute\",\"client\":1,\"tx\":1}\n",
+            ),
+        ],
+    );
+
+    let inputs = expand_inputs(vec![
+        dir.join("day-*.csv").to_string_lossy().into_owned(),
+        dir.join("day-3.jsonl").to_string_lossy().into_owned(),
+    ])
+    .unwrap();
+    let transactions = read_inputs(inputs, None, EnumPrecision::Reject)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(
+        vec![1, 2, 1],
+        transactions
+            .iter()
+            .map(|transaction| transaction.transaction_id)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(EnumType::Dispute, transactions[2].r#type);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn errors_name_the_input_they_come_from() {
+    let dir = shards(
+        "errors",
+        &[("bad.csv", "type,client,tx,amount\ndeposit,1,1,abc\n")],
+    );
+    let bad = dir.join("bad.csv").to_string_lossy().into_owned();
+    let missing = dir.join("missing.csv").to_string_lossy().into_owned();
+
+    let errors: Vec<_> = read_inputs(
+        vec![bad.clone(), missing.clone()],
+        None,
+        EnumPrecision::Reject,
+    )
+    .filter_map(Result::err)
+    .map(|err| err.to_string())
+    .collect();
+
+    assert_eq!(
+        vec![
+            format!("{}: line 2: cannot convert amount 'abc'", bad),
+            format!("cannot open file '{}'", missing),
+        ],
+        errors
+    );
+    assert!(expand_inputs(vec![dir.join("*.jsonl").to_string_lossy().into_owned()]).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn written_positions_are_read_back() {
+    let positions = read_positions(write(two_positions()).as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(two_positions(), positions);
+}
+
+#[test]
+fn every_status_is_read_back() {
+    let positions: Vec<_> = [EnumStatus::Frozen, EnumStatus::Closed]
+        .into_iter()
+        .map(|status| Position {
+            status,
+            ..Position::new(1)
+        })
+        .collect();
+
+    assert_eq!(
+        positions,
+        read_positions(write(positions.clone()).as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    );
+}
+
+#[test]
+fn positions_without_status_use_the_locked_flag() {
+    let statuses: Vec<_> = read_positions(
+        "client,available,held,total,locked
+1,0,0,0,true
+2,0,0,0,false
+"
+        .as_bytes(),
+    )
+    .map(|position| position.unwrap().status)
+    .collect();
+
+    assert_eq!(vec![EnumStatus::Locked, EnumStatus::Active], statuses);
+}
+
+#[test]
+fn opening_positions_must_add_up() {
+    let errors: Vec<_> = read_positions(
+        "client,available,held,total,locked
+1,1.0,0.5,2.0,false
+2,abc,0,0,false
+3,1.0,0,1.0,maybe
+"
+        .as_bytes(),
+    )
+    .filter_map(Result::err)
+    .map(|err| err.to_string())
+    .collect();
+
+    assert_eq!(
+        vec![
+            "line 2: invalid amount '2.0': total is not available plus held",
+            "line 3: cannot convert available 'abc'",
+        ],
+        errors[..2]
+    );
+    assert!(errors[2].starts_with("line 4: invalid csv"));
+}
@@ -0,0 +1,74 @@
+use crate::calculation::{EnumStatus, Policy, Position};
+use crate::engine::{Engine, EnumOrder};
+use crate::parser::{read_transactions, EnumInputFormat, EnumPrecision};
+use crate::snapshot::{load, write, Snapshot};
+use rust_decimal::Decimal;
+
+fn feed(engine: &mut Engine, input: &str) -> Vec<bool> {
+    read_transactions(
+        format!("type,client,tx,amount\n{}", input).as_bytes(),
+        EnumInputFormat::Csv,
+        EnumPrecision::Reject,
+    )
+    .map(|transaction| engine.process(&transaction.unwrap()).is_ok())
+    .collect()
+}
+
+fn round_trip(engine: &Engine) -> Engine {
+    let mut buffer = vec![];
+    write(&mut buffer, &engine.snapshot()).unwrap();
+    Engine::from_snapshot(load(buffer.as_slice()).unwrap(), Policy::default())
+}
+
+#[test]
+fn disputes_refer_to_transactions_of_a_previous_run() {
+    let mut engine = Engine::default();
+    feed(
+        &mut engine,
+        "deposit,2,1,3.0\ndeposit,1,2,1.0\ndeposit,1,3,2.0\ndispute,1,3,\n",
+    );
+
+    let mut engine = round_trip(&engine);
+    // The dispute still open on the locked account can be resolved, a new deposit cannot be made
+    assert_eq!(
+        vec![true, true, false, true],
+        feed(
+            &mut engine,
+            "dispute,1,2,\nchargeback,1,3,\ndeposit,1,1,1.0\nresolve,1,2,\n",
+        )
+    );
+
+    assert_eq!(
+        vec![
+            Position {
+                client: 2,
+                available: Decimal::new(30, 1),
+                held: Decimal::new(0, 1),
+                total: Decimal::new(30, 1),
+                status: EnumStatus::Active,
+            },
+            Position {
+                client: 1,
+                available: Decimal::new(10, 1),
+                held: Decimal::new(0, 1),
+                total: Decimal::new(10, 1),
+                status: EnumStatus::Locked,
+            },
+        ],
+        engine.into_positions(EnumOrder::Appearance)
+    );
+}
+
+#[test]
+fn unknown_version_is_refused() {
+    let err = load::<Snapshot, _>(r#"{"version":0,"positions":[],"store":{}}"#.as_bytes())
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        "invalid snapshot: version 0 is not supported, expected 5",
+        err.to_string()
+    );
+    assert_eq!(65, err.exit_code());
+    assert!(load::<Snapshot, _>("{".as_bytes()).is_err());
+}
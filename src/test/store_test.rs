@@ -0,0 +1,201 @@
+use crate::calculation::EnumRejection;
+use crate::store::{EnumState, StoredTransaction, TransactionStore};
+use crate::{CSVParsed, EnumType};
+use rust_decimal::Decimal;
+
+#[test]
+fn transaction_amount() {
+    let mut store = TransactionStore::default();
+
+    store.insert(&CSVParsed {
+        r#type: EnumType::Deposit,
+        client: 1,
+        transaction_id: 1,
+        amount: Some(Decimal::new(10, 2)),
+        destination: None,
+    });
+    store.insert(&CSVParsed {
+        r#type: EnumType::Withdrawal,
+        client: 1,
+        transaction_id: 2,
+        amount: Some(Decimal::new(3, 2)),
+        destination: None,
+    });
+
+    let transaction = CSVParsed {
+        r#type: EnumType::Chargeback,
+        client: 1,
+        transaction_id: 2,
+        amount: None,
+        destination: None,
+    };
+
+    assert_eq!(
+        Ok(&mut StoredTransaction {
+            client: 1,
+            r#type: EnumType::Withdrawal,
+            amount: Decimal::new(3, 2),
+            disputed: Decimal::ZERO,
+            held: Decimal::ZERO,
+            state: EnumState::Processed,
+        }),
+        store.get_mut(&transaction)
+    );
+
+    let transaction = CSVParsed {
+        r#type: EnumType::Chargeback,
+        client: 1,
+        transaction_id: 5,
+        amount: None,
+        destination: None,
+    };
+
+    assert_eq!(
+        Err(EnumRejection::UnknownTransaction),
+        store.get_mut(&transaction)
+    );
+}
+
+#[test]
+fn transaction_of_another_client_is_not_found() {
+    let mut store = TransactionStore::default();
+
+    store.insert(&CSVParsed {
+        r#type: EnumType::Deposit,
+        client: 1,
+        transaction_id: 1,
+        amount: Some(Decimal::new(10, 2)),
+        destination: None,
+    });
+
+    let transaction = CSVParsed {
+        r#type: EnumType::Dispute,
+        client: 2,
+        transaction_id: 1,
+        amount: None,
+        destination: None,
+    };
+
+    assert_eq!(Err(EnumRejection::WrongClient), store.get_mut(&transaction));
+}
+
+#[test]
+fn rows_without_amount_are_not_stored() {
+    let mut store = TransactionStore::default();
+
+    let transaction = CSVParsed {
+        r#type: EnumType::Dispute,
+        client: 1,
+        transaction_id: 1,
+        amount: None,
+        destination: None,
+    };
+    store.insert(&transaction);
+
+    assert_eq!(
+        Err(EnumRejection::UnknownTransaction),
+        store.get_mut(&transaction)
+    );
+}
+
+#[test]
+fn duplicate_ids_are_rejected_across_clients() {
+    let mut store = TransactionStore::default();
+
+    let deposit = CSVParsed {
+        r#type: EnumType::Deposit,
+        client: 1,
+        transaction_id: 1,
+        amount: Some(Decimal::new(10, 2)),
+        destination: None,
+    };
+    let withdrawal = CSVParsed {
+        r#type: EnumType::Withdrawal,
+        client: 2,
+        transaction_id: 1,
+        amount: Some(Decimal::new(3, 2)),
+        destination: None,
+    };
+    let dispute = CSVParsed {
+        r#type: EnumType::Dispute,
+        client: 1,
+        transaction_id: 1,
+        amount: None,
+        destination: None,
+    };
+
+    assert_eq!(Ok(()), store.register(&deposit));
+    assert_eq!(Ok(()), store.register(&dispute));
+    assert_eq!(
+        Err(EnumRejection::DuplicateTransaction),
+        store.register(&withdrawal)
+    );
+    assert_eq!(
+        Err(EnumRejection::DuplicateTransaction),
+        store.register(&deposit)
+    );
+}
+
+#[test]
+fn ids_belong_to_the_client_that_used_them_first() {
+    let mut store = TransactionStore::default();
+    let transaction = |r#type, client| CSVParsed {
+        r#type,
+        client,
+        transaction_id: 1,
+        amount: None,
+        destination: None,
+    };
+
+    assert_eq!(
+        Ok(()),
+        store.register(&transaction(EnumType::Withdrawal, 1))
+    );
+    assert_eq!(Ok(()), store.register(&transaction(EnumType::Dispute, 1)));
+    assert_eq!(
+        Err(EnumRejection::WrongClient),
+        store.register(&transaction(EnumType::Dispute, 2))
+    );
+    assert_eq!(
+        Err(EnumRejection::DuplicateTransaction),
+        store.register(&transaction(EnumType::Deposit, 2))
+    );
+}
+
+#[test]
+fn dispute_is_settled_held_funds_first() {
+    let mut stored = StoredTransaction {
+        client: 1,
+        r#type: EnumType::Deposit,
+        amount: Decimal::new(10, 0),
+        disputed: Decimal::new(10, 0),
+        held: Decimal::new(4, 0),
+        state: EnumState::Disputed,
+    };
+    let settle = |amount| CSVParsed {
+        r#type: EnumType::Resolve,
+        client: 1,
+        transaction_id: 1,
+        amount,
+        destination: None,
+    };
+
+    assert_eq!(
+        Ok((Decimal::new(6, 0), Decimal::new(4, 0))),
+        stored.settle(&settle(Some(Decimal::new(6, 0))), EnumState::Resolved)
+    );
+    assert_eq!(EnumState::Disputed, stored.state);
+    assert_eq!(
+        Err(EnumRejection::ExceedsDispute),
+        stored.settle(&settle(Some(Decimal::new(5, 0))), EnumState::Resolved)
+    );
+    assert_eq!(
+        Ok((Decimal::new(4, 0), Decimal::ZERO)),
+        stored.settle(&settle(None), EnumState::Resolved)
+    );
+    assert_eq!(EnumState::Resolved, stored.state);
+    assert_eq!(
+        Err(EnumRejection::DisputeNotOpen),
+        stored.settle(&settle(None), EnumState::Resolved)
+    );
+}